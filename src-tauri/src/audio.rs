@@ -3,10 +3,11 @@ use cpal::{Device, SampleFormat, StreamConfig};
 use serde::Serialize;
 use std::io::Cursor;
 use std::sync::{
-    atomic::{AtomicBool, Ordering},
+    atomic::{AtomicBool, AtomicU32, Ordering},
     Arc, Mutex,
 };
 use std::thread;
+use std::time::Duration;
 
 use crate::error::AppError;
 
@@ -78,6 +79,10 @@ struct RecordingBuffer {
     source_sample_rate: u32,
     source_channels: u16,
     is_recording: AtomicBool,
+    /// RMS of the most recent block of samples, as `f32` bits.
+    level: AtomicU32,
+    /// Samples beyond this are dropped.
+    max_samples: usize,
 }
 
 /// Thread-safe audio recorder that manages recording on a dedicated thread.
@@ -99,7 +104,13 @@ impl AudioRecorder {
         }
     }
 
-    pub fn start_recording(&mut self, device_index: Option<usize>) -> Result<(), AppError> {
+    /// Start recording from the given device (default: the system default).
+    /// Audio beyond `max_duration` is dropped.
+    pub fn start_recording(
+        &mut self,
+        device_index: Option<usize>,
+        max_duration: Option<Duration>,
+    ) -> Result<(), AppError> {
         if self.buffer.is_some() {
             return Err(AppError::Audio("Already recording".into()));
         }
@@ -120,6 +131,10 @@ impl AudioRecorder {
             source_sample_rate,
             source_channels,
             is_recording: AtomicBool::new(true),
+            level: AtomicU32::new(0),
+            max_samples: max_duration.map_or(usize::MAX, |d| {
+                (d.as_secs_f64() * source_sample_rate as f64) as usize * source_channels as usize
+            }),
         });
 
         let buf_clone = Arc::clone(&buffer);
@@ -162,7 +177,21 @@ impl AudioRecorder {
         Ok(())
     }
 
+    /// Current input level (RMS, 0 to 1), or `None` when not recording.
+    pub fn level(&self) -> Option<f32> {
+        self.buffer
+            .as_ref()
+            .map(|buffer| f32::from_bits(buffer.level.load(Ordering::Relaxed)))
+    }
+
     pub fn stop_recording(&mut self) -> Result<Vec<u8>, AppError> {
+        self.finish_recording()?.into_wav()
+    }
+
+    /// Signal the recording to stop and hand it over without waiting for it,
+    /// so the recorder is free for the next recording right away. The audio
+    /// is encoded later by `FinishedRecording::into_wav`.
+    pub fn finish_recording(&mut self) -> Result<FinishedRecording, AppError> {
         let buffer = self
             .buffer
             .take()
@@ -171,14 +200,31 @@ impl AudioRecorder {
         // Signal the recording thread to stop
         buffer.is_recording.store(false, Ordering::Relaxed);
 
-        // Wait for the recording thread to finish
-        if let Some(handle) = self.recording_thread.take() {
+        Ok(FinishedRecording {
+            buffer,
+            recording_thread: self.recording_thread.take(),
+        })
+    }
+}
+
+/// A recording that was told to stop but not yet encoded; see
+/// `AudioRecorder::finish_recording`.
+pub struct FinishedRecording {
+    buffer: Arc<RecordingBuffer>,
+    recording_thread: Option<thread::JoinHandle<()>>,
+}
+
+impl FinishedRecording {
+    /// Wait for the recording thread and encode the audio as 16 kHz mono WAV.
+    /// Blocking: joins a thread and processes the whole recording.
+    pub fn into_wav(self) -> Result<Vec<u8>, AppError> {
+        if let Some(handle) = self.recording_thread {
             let _ = handle.join();
         }
 
-        let samples = buffer.samples.lock().unwrap();
-        let mono_samples = to_mono(&samples, buffer.source_channels);
-        let resampled = resample(&mono_samples, buffer.source_sample_rate, TARGET_SAMPLE_RATE);
+        let samples = self.buffer.samples.lock().unwrap();
+        let mono_samples = to_mono(&samples, self.buffer.source_channels);
+        let resampled = resample(&mono_samples, self.buffer.source_sample_rate, TARGET_SAMPLE_RATE);
 
         encode_wav(&resampled, TARGET_SAMPLE_RATE, TARGET_CHANNELS)
     }
@@ -200,8 +246,10 @@ where
             move |data: &[T], _: &cpal::InputCallbackInfo| {
                 let float_samples: Vec<f32> =
                     data.iter().map(|s| cpal::Sample::from_sample(*s)).collect();
+                buf.level.store(rms(&float_samples).to_bits(), Ordering::Relaxed);
                 if let Ok(mut guard) = buf.samples.lock() {
-                    guard.extend_from_slice(&float_samples);
+                    let room = buf.max_samples.saturating_sub(guard.len());
+                    guard.extend_from_slice(&float_samples[..float_samples.len().min(room)]);
                 }
             },
             |err| {
@@ -214,6 +262,13 @@ where
     Ok(stream)
 }
 
+fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+}
+
 fn to_mono(samples: &[f32], channels: u16) -> Vec<f32> {
     if channels == 1 {
         return samples.to_vec();
@@ -30,6 +30,24 @@ pub enum AppError {
     Other(String),
 }
 
+impl AppError {
+    /// Stable machine-readable name of the variant, sent with pipeline
+    /// error events so listeners don't have to parse the message.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Audio(_) => "audio",
+            AppError::Whisper(_) => "whisper",
+            AppError::Llm(_) => "llm",
+            AppError::Settings(_) => "settings",
+            AppError::History(_) => "history",
+            AppError::Hotkey(_) => "hotkey",
+            AppError::Output(_) => "output",
+            AppError::Io(_) => "io",
+            AppError::Other(_) => "other",
+        }
+    }
+}
+
 impl Serialize for AppError {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -0,0 +1,272 @@
+//! Regression harness for the cleanup prompt.
+//!
+//! Runs the corpus in `eval/corpus.yaml` through `cleanup_text` and scores
+//! each result for faithfulness (`check_faithfulness`), kept phrases (hedges,
+//! names, key content), resistance to instructions spoken in the
+//! transcription, and word overlap with a reference cleanup. A Markdown
+//! report is written so prompt changes can be compared run to run.
+//!
+//! The harness itself is tested offline against a scripted mock backend. To
+//! evaluate a real model (the test is `#[ignore]`d since it needs one):
+//!
+//! ```text
+//! LLM_EVAL_ENDPOINT=http://localhost:11434 LLM_EVAL_MODEL=mistral \
+//!     cargo test evaluate_endpoint -- --ignored --nocapture
+//! ```
+//!
+//! Optional: `LLM_EVAL_API_TYPE` (`ollama`, `openai`, `anthropic`,
+//! `llamacpp`, `lmstudio`), `LLM_EVAL_API_KEY`, `LLM_EVAL_PROMPT_FILE` (a
+//! system prompt to test instead of the default) and `LLM_EVAL_REPORT`
+//! (report path, default `target/llm-eval-report.md`).
+
+use serde::Deserialize;
+use std::fmt::Write as _;
+
+use crate::error::AppError;
+use crate::llm::{self, LlmConfig};
+
+pub const CORPUS: &str = include_str!("../eval/corpus.yaml");
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EvalCase {
+    pub name: String,
+    pub category: String,
+    pub raw: String,
+    pub expected: String,
+    #[serde(default)]
+    pub must_keep: Vec<String>,
+    #[serde(default)]
+    pub must_not_contain: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CaseResult {
+    pub case: EvalCase,
+    /// The model's output, or the request error.
+    pub output: Result<String, String>,
+    /// Reasons the case failed; empty when it passed.
+    pub failures: Vec<String>,
+    /// Word overlap with `expected`, 0 to 1.
+    pub similarity: f32,
+}
+
+impl CaseResult {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+pub fn load_corpus(contents: &str) -> Result<Vec<EvalCase>, AppError> {
+    serde_yaml::from_str(contents).map_err(|e| AppError::Llm(format!("Invalid eval corpus: {}", e)))
+}
+
+pub fn score(case: &EvalCase, output: Result<String, AppError>) -> CaseResult {
+    let output = output.map_err(|e| e.to_string());
+    let mut failures = Vec::new();
+    let mut similarity = 0.0;
+
+    match &output {
+        Err(e) => failures.push(format!("request failed: {}", e)),
+        Ok(text) => {
+            if let Err(reason) = llm::check_faithfulness(&case.raw, text) {
+                failures.push(format!("unfaithful: {}", reason));
+            }
+            let lower = text.to_lowercase();
+            for phrase in &case.must_keep {
+                if !lower.contains(&phrase.to_lowercase()) {
+                    failures.push(format!("missing {:?}", phrase));
+                }
+            }
+            for phrase in &case.must_not_contain {
+                if lower.contains(&phrase.to_lowercase()) {
+                    failures.push(format!("contains {:?}", phrase));
+                }
+            }
+            similarity = crate::few_shot::text_similarity(text, &case.expected);
+        }
+    }
+
+    CaseResult {
+        case: case.clone(),
+        output,
+        failures,
+        similarity,
+    }
+}
+
+/// Run every case through `cleanup_text`, one at a time.
+pub async fn run(config: &LlmConfig, cases: &[EvalCase]) -> Vec<CaseResult> {
+    let mut results = Vec::new();
+    for case in cases {
+        let output = llm::cleanup_text(config, &case.raw).await;
+        results.push(score(case, output));
+    }
+    results
+}
+
+pub fn report(config: &LlmConfig, results: &[CaseResult]) -> String {
+    let passed = results.iter().filter(|r| r.passed()).count();
+    let mean_similarity = if results.is_empty() {
+        0.0
+    } else {
+        results.iter().map(|r| r.similarity).sum::<f32>() / results.len() as f32
+    };
+
+    let mut out = String::new();
+    let _ = writeln!(out, "# LLM cleanup eval\n");
+    let _ = writeln!(out, "- Endpoint: {} ({:?})", config.endpoint, config.api_type);
+    let _ = writeln!(out, "- Model: {}", config.model);
+    let _ = writeln!(out, "- Passed: {}/{}", passed, results.len());
+    let _ = writeln!(out, "- Mean similarity to reference: {:.2}\n", mean_similarity);
+
+    let mut categories: Vec<&str> = results.iter().map(|r| r.case.category.as_str()).collect();
+    categories.sort_unstable();
+    categories.dedup();
+    let _ = writeln!(out, "| Category | Passed |\n|---|---|");
+    for category in categories {
+        let in_category: Vec<&CaseResult> =
+            results.iter().filter(|r| r.case.category == category).collect();
+        let passed = in_category.iter().filter(|r| r.passed()).count();
+        let _ = writeln!(out, "| {} | {}/{} |", category, passed, in_category.len());
+    }
+
+    let _ = writeln!(out, "\n## Cases\n");
+    for r in results {
+        let verdict = if r.passed() { "PASS" } else { "FAIL" };
+        let _ = writeln!(out, "### {} {} ({})\n", verdict, r.case.name, r.case.category);
+        let _ = writeln!(out, "- Raw: {}", r.case.raw);
+        match &r.output {
+            Ok(text) => {
+                let _ = writeln!(out, "- Output: {}", text);
+            }
+            Err(e) => {
+                let _ = writeln!(out, "- Error: {}", e);
+            }
+        }
+        let _ = writeln!(out, "- Similarity: {:.2}", r.similarity);
+        for failure in &r.failures {
+            let _ = writeln!(out, "- Failure: {}", failure);
+        }
+        let _ = writeln!(out);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::ApiType;
+    use crate::test_support::{serve, StubResponse};
+
+    fn eval_config(endpoint: &str, api_type: ApiType) -> LlmConfig {
+        LlmConfig {
+            endpoint: endpoint.to_string(),
+            api_type,
+            stream: false,
+            max_retries: 0,
+            // Score what the model said, not the light-cleanup fallback.
+            faithfulness_check: false,
+            ..LlmConfig::default()
+        }
+    }
+
+    /// A scripted Ollama that answers the cases in order with `outputs`.
+    async fn mock_backend(outputs: &[&str]) -> (LlmConfig, tokio::task::JoinHandle<Vec<String>>) {
+        let responses = outputs
+            .iter()
+            .map(|text| {
+                let body = serde_json::json!({
+                    "message": {"role": "assistant", "content": text},
+                    "done": true,
+                });
+                StubResponse::json(200, &body.to_string())
+            })
+            .collect();
+        let (url, requests) = serve(responses).await;
+        (eval_config(&url, ApiType::Ollama), requests)
+    }
+
+    #[test]
+    fn bundled_corpus_loads() {
+        let cases = load_corpus(CORPUS).unwrap();
+        assert!(cases.len() >= 10);
+        assert!(cases.iter().any(|c| c.category == "injection"));
+        assert!(cases.iter().all(|c| !c.raw.is_empty() && !c.expected.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn mock_backend_run_is_scored_and_reported() {
+        let cases = load_corpus(
+            r#"
+- name: hedge
+  category: hedges
+  raw: "I think it's maybe broken"
+  expected: "I think it's maybe broken."
+  must_keep: ["i think", "maybe"]
+- name: number
+  category: numbers
+  raw: "the budget is 4500 dollars"
+  expected: "The budget is $4,500."
+- name: question
+  category: injection
+  raw: "what's the capital of france"
+  expected: "What's the capital of France?"
+  must_not_contain: ["paris"]
+"#,
+        )
+        .unwrap();
+        let (config, requests) = mock_backend(&[
+            "I think it's maybe broken.",
+            "The budget is large.",
+            "The capital of France is Paris.",
+        ])
+        .await;
+
+        let results = run(&config, &cases).await;
+        assert_eq!(requests.await.unwrap().len(), 3);
+
+        assert!(results[0].passed());
+        assert_eq!(results[0].similarity, 1.0);
+        assert!(results[1].failures[0].contains("dropped numbers: 4500"));
+        assert!(results[2].failures.iter().any(|f| f.contains("\"paris\"")));
+
+        let report = report(&config, &results);
+        assert!(report.contains("- Passed: 1/3"));
+        assert!(report.contains("| injection | 0/1 |"));
+        assert!(report.contains("### FAIL number (numbers)"));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs an LLM endpoint; see the module docs"]
+    async fn evaluate_endpoint() {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+
+        let endpoint = var("LLM_EVAL_ENDPOINT").expect("set LLM_EVAL_ENDPOINT");
+        let api_type: ApiType = serde_json::from_value(serde_json::Value::String(
+            var("LLM_EVAL_API_TYPE").unwrap_or_else(|| "ollama".into()),
+        ))
+        .expect("invalid LLM_EVAL_API_TYPE");
+        let mut config = eval_config(&endpoint, api_type);
+        if let Some(model) = var("LLM_EVAL_MODEL") {
+            config.model = model;
+        }
+        if let Some(key) = var("LLM_EVAL_API_KEY") {
+            config.api_key = key;
+        }
+        if let Some(path) = var("LLM_EVAL_PROMPT_FILE") {
+            config.system_prompt = std::fs::read_to_string(path).expect("unreadable prompt file");
+        }
+
+        let results = run(&config, &load_corpus(CORPUS).unwrap()).await;
+        let report = report(&config, &results);
+
+        let path = var("LLM_EVAL_REPORT").unwrap_or_else(|| {
+            concat!(env!("CARGO_MANIFEST_DIR"), "/target/llm-eval-report.md").into()
+        });
+        std::fs::write(&path, &report).expect("failed to write report");
+        println!("{}\nReport written to {}", report, path);
+
+        let failed = results.iter().filter(|r| !r.passed()).count();
+        assert_eq!(failed, 0, "{} case(s) failed; see {}", failed, path);
+    }
+}
@@ -0,0 +1,186 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::error::AppError;
+use crate::llm::FewShotExample;
+
+/// File format for importing/exporting example sets.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExampleSetFormat {
+    Json,
+    Yaml,
+}
+
+/// How examples are picked when there are more than `few_shot_limit`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FewShotSelection {
+    /// The first examples in the list.
+    First,
+    /// The examples whose input shares the most words with the transcription.
+    #[default]
+    Similar,
+}
+
+/// What an exported file contains. A bare list of examples is accepted on
+/// import too.
+#[derive(Serialize, Deserialize)]
+struct ExampleSet {
+    examples: Vec<FewShotExample>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ExampleSetFile {
+    Set(ExampleSet),
+    List(Vec<FewShotExample>),
+}
+
+pub fn export(examples: &[FewShotExample], format: ExampleSetFormat) -> Result<String, AppError> {
+    let set = ExampleSet {
+        examples: examples.to_vec(),
+    };
+    match format {
+        ExampleSetFormat::Json => serde_json::to_string_pretty(&set).map_err(|e| e.to_string()),
+        ExampleSetFormat::Yaml => serde_yaml::to_string(&set).map_err(|e| e.to_string()),
+    }
+    .map_err(|e| AppError::Settings(format!("Failed to export examples: {}", e)))
+}
+
+pub fn import(contents: &str, format: ExampleSetFormat) -> Result<Vec<FewShotExample>, AppError> {
+    let file: ExampleSetFile = match format {
+        ExampleSetFormat::Json => serde_json::from_str(contents).map_err(|e| e.to_string()),
+        ExampleSetFormat::Yaml => serde_yaml::from_str(contents).map_err(|e| e.to_string()),
+    }
+    .map_err(|e| AppError::Settings(format!("Invalid example set: {}", e)))?;
+
+    let examples = match file {
+        ExampleSetFile::Set(set) => set.examples,
+        ExampleSetFile::List(list) => list,
+    };
+    if let Some(i) = examples
+        .iter()
+        .position(|e| e.input.trim().is_empty() || e.output.trim().is_empty())
+    {
+        return Err(AppError::Settings(format!(
+            "Example {} has an empty input or output",
+            i + 1
+        )));
+    }
+    Ok(examples)
+}
+
+/// Append `new` to `existing`. An example whose input is already present
+/// replaces the old one in place, so re-importing a corrected set updates it.
+pub fn merge(existing: &mut Vec<FewShotExample>, new: Vec<FewShotExample>) {
+    for example in new {
+        match existing.iter_mut().find(|e| e.input.trim() == example.input.trim()) {
+            Some(slot) => *slot = example,
+            None => existing.push(example),
+        }
+    }
+}
+
+/// Pick at most `limit` examples (0 = all) to send with a transcription,
+/// keeping their original order.
+pub fn select<'a>(
+    examples: &'a [FewShotExample],
+    raw_text: &str,
+    limit: usize,
+    selection: FewShotSelection,
+) -> Vec<&'a FewShotExample> {
+    if limit == 0 || examples.len() <= limit {
+        return examples.iter().collect();
+    }
+    match selection {
+        FewShotSelection::First => examples.iter().take(limit).collect(),
+        FewShotSelection::Similar => {
+            let words = word_set(raw_text);
+            let mut ranked: Vec<(usize, f32)> = examples
+                .iter()
+                .enumerate()
+                .map(|(i, e)| (i, similarity(&words, &word_set(&e.input))))
+                .collect();
+            // Stable sort: ties keep list order.
+            ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+            let mut picked: Vec<usize> = ranked.into_iter().take(limit).map(|(i, _)| i).collect();
+            picked.sort_unstable();
+            picked.into_iter().map(|i| &examples[i]).collect()
+        }
+    }
+}
+
+/// Word-overlap similarity of two texts, from 0 (nothing shared) to 1.
+pub fn text_similarity(a: &str, b: &str) -> f32 {
+    similarity(&word_set(a), &word_set(b))
+}
+
+fn word_set(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Jaccard similarity of two word sets.
+fn similarity(a: &HashSet<String>, b: &HashSet<String>) -> f32 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f32 / union as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ex(input: &str, output: &str) -> FewShotExample {
+        FewShotExample {
+            input: input.to_string(),
+            output: output.to_string(),
+        }
+    }
+
+    #[test]
+    fn json_and_yaml_round_trip() {
+        let examples = vec![ex("um hi there", "Hi there."), ex("so like yeah", "Yeah.")];
+        for format in [ExampleSetFormat::Json, ExampleSetFormat::Yaml] {
+            let text = export(&examples, format).unwrap();
+            assert_eq!(import(&text, format).unwrap(), examples);
+        }
+    }
+
+    #[test]
+    fn bare_lists_are_accepted_and_empty_examples_rejected() {
+        let yaml = "- input: uh ok\n  output: OK.\n";
+        assert_eq!(import(yaml, ExampleSetFormat::Yaml).unwrap(), vec![ex("uh ok", "OK.")]);
+
+        let json = r#"{"examples": [{"input": "hi", "output": "  "}]}"#;
+        assert!(import(json, ExampleSetFormat::Json).is_err());
+    }
+
+    #[test]
+    fn merge_replaces_matching_inputs() {
+        let mut examples = vec![ex("a", "A."), ex("b", "B")];
+        merge(&mut examples, vec![ex("b", "B."), ex("c", "C.")]);
+        assert_eq!(examples, vec![ex("a", "A."), ex("b", "B."), ex("c", "C.")]);
+    }
+
+    #[test]
+    fn similar_selection_prefers_overlapping_examples_in_list_order() {
+        let examples = vec![
+            ex("send the invoice to the client", "Send the invoice to the client."),
+            ex("um what time is the standup", "What time is the standup?"),
+            ex("the client wants the invoice by friday", "The client wants the invoice by Friday."),
+        ];
+        let picked = select(&examples, "did the client get the invoice", 2, FewShotSelection::Similar);
+        assert_eq!(picked, vec![&examples[0], &examples[2]]);
+
+        let picked = select(&examples, "did the client get the invoice", 2, FewShotSelection::First);
+        assert_eq!(picked, vec![&examples[0], &examples[1]]);
+
+        assert_eq!(select(&examples, "anything", 0, FewShotSelection::Similar).len(), 3);
+    }
+}
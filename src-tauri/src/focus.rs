@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+
+/// The window that had focus when recording started, i.e. where the text is
+/// going to be pasted.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct FocusedApp {
+    /// Application name or window class, e.g. "Slack", "org.wezfurlong.wezterm".
+    pub app: String,
+    pub title: String,
+}
+
+/// Query the focused window. Returns `None` when it can't be determined
+/// (Wayland, missing `xdotool`, no Accessibility permission, unsupported OS).
+pub fn capture() -> Option<FocusedApp> {
+    let focused = query()?;
+    if focused.app.is_empty() && focused.title.is_empty() {
+        return None;
+    }
+    Some(focused)
+}
+
+#[cfg(target_os = "linux")]
+fn query() -> Option<FocusedApp> {
+    // xdotool only sees X11 (and XWayland) windows.
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() && std::env::var_os("DISPLAY").is_none() {
+        return None;
+    }
+    Some(FocusedApp {
+        app: run("xdotool", &["getactivewindow", "getwindowclassname"])?,
+        title: run("xdotool", &["getactivewindow", "getwindowname"]).unwrap_or_default(),
+    })
+}
+
+#[cfg(target_os = "macos")]
+fn query() -> Option<FocusedApp> {
+    const SCRIPT: &str = r#"tell application "System Events"
+    set frontApp to first application process whose frontmost is true
+    set appName to name of frontApp
+    set windowTitle to ""
+    try
+        set windowTitle to name of front window of frontApp
+    end try
+end tell
+return appName & linefeed & windowTitle"#;
+
+    let output = run("osascript", &["-e", SCRIPT])?;
+    let (app, title) = output.split_once('\n').unwrap_or((output.as_str(), ""));
+    Some(FocusedApp {
+        app: app.trim().to_string(),
+        title: title.trim().to_string(),
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn query() -> Option<FocusedApp> {
+    None
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
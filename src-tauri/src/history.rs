@@ -1,5 +1,6 @@
 use rusqlite::{params, Connection};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::sync::Mutex;
 
 use crate::error::AppError;
@@ -10,8 +11,46 @@ pub struct TranscriptionRecord {
     pub raw_text: String,
     pub cleaned_text: String,
     pub created_at: String,
+    /// Length of the recorded audio.
     pub duration_secs: f64,
+    /// Transcription backend.
     pub model_used: String,
+    /// LLM used for cleanup, empty if cleanup wasn't attempted.
+    #[serde(default)]
+    pub llm_model: String,
+    /// Milliseconds spent per step: "stop", "transcribe", each pipeline
+    /// stage before the history stage by name ("cleanup", "output", ...),
+    /// and "total" (their sum).
+    #[serde(default)]
+    pub timings: BTreeMap<String, u64>,
+}
+
+/// Latency percentiles of one pipeline step for one model or backend.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct LatencyStats {
+    pub step: String,
+    /// The LLM model for "cleanup", the transcription backend otherwise.
+    pub model: String,
+    pub count: usize,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+}
+
+const SELECT_COLUMNS: &str =
+    "SELECT id, raw_text, cleaned_text, created_at, duration_secs, model_used, llm_model, timings FROM transcriptions";
+
+fn record_from_row(row: &rusqlite::Row) -> rusqlite::Result<TranscriptionRecord> {
+    let timings: String = row.get(7)?;
+    Ok(TranscriptionRecord {
+        id: row.get(0)?,
+        raw_text: row.get(1)?,
+        cleaned_text: row.get(2)?,
+        created_at: row.get(3)?,
+        duration_secs: row.get(4)?,
+        model_used: row.get(5)?,
+        llm_model: row.get(6)?,
+        timings: serde_json::from_str(&timings).unwrap_or_default(),
+    })
 }
 
 pub struct HistoryDb {
@@ -39,6 +78,7 @@ impl HistoryDb {
             [],
         )
         .map_err(|e| AppError::History(format!("Failed to create table: {}", e)))?;
+        migrate(&conn)?;
 
         Ok(Self {
             conn: Mutex::new(conn),
@@ -48,8 +88,8 @@ impl HistoryDb {
     pub fn insert(&self, record: &TranscriptionRecord) -> Result<(), AppError> {
         let conn = self.conn.lock().unwrap();
         conn.execute(
-            "INSERT INTO transcriptions (id, raw_text, cleaned_text, created_at, duration_secs, model_used)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            "INSERT INTO transcriptions (id, raw_text, cleaned_text, created_at, duration_secs, model_used, llm_model, timings)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
             params![
                 record.id,
                 record.raw_text,
@@ -57,6 +97,8 @@ impl HistoryDb {
                 record.created_at,
                 record.duration_secs,
                 record.model_used,
+                record.llm_model,
+                serde_json::to_string(&record.timings).unwrap_or_else(|_| "{}".into()),
             ],
         )
         .map_err(|e| AppError::History(format!("Insert failed: {}", e)))?;
@@ -66,20 +108,11 @@ impl HistoryDb {
     pub fn list(&self) -> Result<Vec<TranscriptionRecord>, AppError> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn
-            .prepare("SELECT id, raw_text, cleaned_text, created_at, duration_secs, model_used FROM transcriptions ORDER BY created_at DESC")
+            .prepare(&format!("{} ORDER BY created_at DESC", SELECT_COLUMNS))
             .map_err(|e| AppError::History(e.to_string()))?;
 
         let records = stmt
-            .query_map([], |row| {
-                Ok(TranscriptionRecord {
-                    id: row.get(0)?,
-                    raw_text: row.get(1)?,
-                    cleaned_text: row.get(2)?,
-                    created_at: row.get(3)?,
-                    duration_secs: row.get(4)?,
-                    model_used: row.get(5)?,
-                })
-            })
+            .query_map([], record_from_row)
             .map_err(|e| AppError::History(e.to_string()))?
             .filter_map(|r| r.ok())
             .collect();
@@ -87,6 +120,16 @@ impl HistoryDb {
         Ok(records)
     }
 
+    pub fn get(&self, id: &str) -> Result<TranscriptionRecord, AppError> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            &format!("{} WHERE id = ?1", SELECT_COLUMNS),
+            params![id],
+            record_from_row,
+        )
+        .map_err(|e| AppError::History(format!("History item {} not found: {}", id, e)))
+    }
+
     pub fn delete(&self, id: &str) -> Result<(), AppError> {
         let conn = self.conn.lock().unwrap();
         conn.execute("DELETE FROM transcriptions WHERE id = ?1", params![id])
@@ -113,3 +156,132 @@ impl HistoryDb {
         Ok(())
     }
 }
+
+/// Add the columns of newer versions to a database created by an older one.
+fn migrate(conn: &Connection) -> Result<(), AppError> {
+    let columns: Vec<String> = conn
+        .prepare("SELECT name FROM pragma_table_info('transcriptions')")
+        .and_then(|mut stmt| stmt.query_map([], |row| row.get(0))?.collect())
+        .map_err(|e| AppError::History(e.to_string()))?;
+    for (name, definition) in [
+        ("llm_model", "TEXT NOT NULL DEFAULT ''"),
+        ("timings", "TEXT NOT NULL DEFAULT '{}'"),
+    ] {
+        if !columns.iter().any(|c| c == name) {
+            conn.execute(
+                &format!("ALTER TABLE transcriptions ADD COLUMN {} {}", name, definition),
+                [],
+            )
+            .map_err(|e| AppError::History(format!("Migration failed: {}", e)))?;
+        }
+    }
+    Ok(())
+}
+
+/// p50/p95 latency per step and model over `records`, sorted by step and
+/// model.
+pub fn latency_stats(records: &[TranscriptionRecord]) -> Vec<LatencyStats> {
+    let mut samples: BTreeMap<(String, String), Vec<u64>> = BTreeMap::new();
+    for record in records {
+        for (step, &ms) in &record.timings {
+            let model = if step == "cleanup" {
+                &record.llm_model
+            } else {
+                &record.model_used
+            };
+            samples
+                .entry((step.clone(), model.clone()))
+                .or_default()
+                .push(ms);
+        }
+    }
+
+    samples
+        .into_iter()
+        .map(|((step, model), mut values)| {
+            values.sort_unstable();
+            LatencyStats {
+                step,
+                model,
+                count: values.len(),
+                p50_ms: percentile(&values, 50),
+                p95_ms: percentile(&values, 95),
+            }
+        })
+        .collect()
+}
+
+/// Nearest-rank percentile of sorted, non-empty `values`.
+fn percentile(values: &[u64], p: usize) -> u64 {
+    let rank = (p * values.len()).div_ceil(100).max(1);
+    values[rank - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(model: &str, llm: &str, timings: &[(&str, u64)]) -> TranscriptionRecord {
+        TranscriptionRecord {
+            id: uuid::Uuid::new_v4().to_string(),
+            raw_text: "raw".to_string(),
+            cleaned_text: "clean".to_string(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+            duration_secs: 1.5,
+            model_used: model.to_string(),
+            llm_model: llm.to_string(),
+            timings: timings.iter().map(|(k, v)| (k.to_string(), *v)).collect(),
+        }
+    }
+
+    #[test]
+    fn old_databases_are_migrated_and_timings_round_trip() {
+        let dir = std::env::temp_dir().join(format!("history-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        Connection::open(dir.join("history.db"))
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE transcriptions (id TEXT PRIMARY KEY, raw_text TEXT NOT NULL,
+                 cleaned_text TEXT NOT NULL, created_at TEXT NOT NULL,
+                 duration_secs REAL NOT NULL, model_used TEXT NOT NULL);
+                 INSERT INTO transcriptions VALUES ('old', 'a', 'A.', '2024-01-01T00:00:00Z', 0.2, 'tiny');",
+            )
+            .unwrap();
+
+        let db = HistoryDb::new(&dir).unwrap();
+        let old = db.get("old").unwrap();
+        assert!(old.timings.is_empty() && old.llm_model.is_empty());
+
+        let new = record("base", "mistral", &[("transcribe", 300), ("cleanup", 800)]);
+        db.insert(&new).unwrap();
+        let stored = db.get(&new.id).unwrap();
+        assert_eq!(stored.timings, new.timings);
+        assert_eq!(stored.llm_model, "mistral");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn stats_group_cleanup_by_llm_and_other_steps_by_backend() {
+        let mut records: Vec<TranscriptionRecord> = (1..=20)
+            .map(|i| record("base", "mistral", &[("transcribe", i * 10), ("cleanup", i * 100)]))
+            .collect();
+        records.push(record("whisper-1 (API)", "", &[("transcribe", 50)]));
+
+        let stats = latency_stats(&records);
+        let find = |step: &str, model: &str| stats.iter().find(|s| s.step == step && s.model == model).unwrap();
+        assert_eq!(
+            *find("cleanup", "mistral"),
+            LatencyStats {
+                step: "cleanup".into(),
+                model: "mistral".into(),
+                count: 20,
+                p50_ms: 1000,
+                p95_ms: 1900,
+            }
+        );
+        assert_eq!(find("transcribe", "base").p95_ms, 190);
+        assert_eq!(find("transcribe", "whisper-1 (API)").p50_ms, 50);
+        assert_eq!(stats.len(), 3);
+    }
+}
@@ -2,11 +2,13 @@ use std::collections::HashSet;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
 
 use rdev::{EventType, Key};
 use tauri::{AppHandle, Emitter, Manager};
 
-use crate::pipeline::{self, PipelineStatus, PipelineStatusEvent};
+use crate::pipeline::{self, PipelineMode, PipelineStatus, PipelineStatusEvent};
+use crate::run_queue::QueuedRun;
 use crate::sounds;
 use crate::tray;
 use crate::AppState;
@@ -14,8 +16,15 @@ use crate::AppState;
 pub struct HotkeyState {
     /// The keys that make up the configured hotkey combo
     combo: Mutex<Vec<Key>>,
-    /// Whether the combo is currently held down (recording active)
-    combo_active: AtomicBool,
+    /// The keys of the rewrite-mode combo (empty = disabled)
+    rewrite_combo: Mutex<Vec<Key>>,
+    /// Which combo is currently held down (recording active), if any
+    active: Mutex<Option<PipelineMode>>,
+    /// Id of the latest run, assigned when its recording starts
+    run_id: Mutex<String>,
+    /// Set when a recording hit the maximum length while its combo was
+    /// still held; the combo is ignored until it is released.
+    cut_off: AtomicBool,
     /// Paused during UI hotkey recording to prevent conflicts
     paused: AtomicBool,
     /// Whether a listener thread is currently alive
@@ -23,14 +32,22 @@ pub struct HotkeyState {
 }
 
 impl HotkeyState {
-    pub fn new(combo: Vec<Key>) -> Self {
+    pub fn new(combo: Vec<Key>, rewrite_combo: Vec<Key>) -> Self {
         Self {
             combo: Mutex::new(combo),
-            combo_active: AtomicBool::new(false),
+            rewrite_combo: Mutex::new(rewrite_combo),
+            active: Mutex::new(None),
+            run_id: Mutex::new(String::new()),
+            cut_off: AtomicBool::new(false),
             paused: AtomicBool::new(false),
             listener_running: AtomicBool::new(false),
         }
     }
+
+    /// Whether a hotkey recording is in progress.
+    pub fn is_recording(&self) -> bool {
+        self.active.lock().unwrap().is_some()
+    }
 }
 
 /// Spawn the global key-event listener thread if one isn't already running.
@@ -90,31 +107,104 @@ fn check_combo(held_keys: &HashSet<Key>, state: &Arc<HotkeyState>, app: &AppHand
         return;
     }
 
-    let combo = state.combo.lock().unwrap();
-    if combo.is_empty() {
-        return;
-    }
+    let combo = state.combo.lock().unwrap().clone();
+    let rewrite_combo = state.rewrite_combo.lock().unwrap().clone();
+    let held = |keys: &[Key]| !keys.is_empty() && keys.iter().all(|k| held_keys.contains(k));
 
-    let all_held = combo.iter().all(|k| held_keys.contains(k));
-    drop(combo);
-
-    let was_active = state.combo_active.load(Ordering::Relaxed);
-
-    if all_held && !was_active {
-        state.combo_active.store(true, Ordering::Relaxed);
-        on_hotkey_pressed(app);
-    } else if !all_held && was_active {
-        state.combo_active.store(false, Ordering::Relaxed);
-        on_hotkey_released(app);
+    let mut active = state.active.lock().unwrap();
+    match *active {
+        None if state.cut_off.load(Ordering::SeqCst) => {
+            if !held(&combo) && !held(&rewrite_combo) {
+                state.cut_off.store(false, Ordering::SeqCst);
+            }
+        }
+        None => {
+            // If one combo contains the other, the longer one wins once all
+            // of its keys are down.
+            let mode = match (held(&combo), held(&rewrite_combo)) {
+                (true, true) if rewrite_combo.len() > combo.len() => PipelineMode::Rewrite,
+                (true, _) => PipelineMode::Dictate,
+                (false, true) => PipelineMode::Rewrite,
+                (false, false) => return,
+            };
+            *active = Some(mode);
+            drop(active);
+            on_hotkey_pressed(app, mode);
+        }
+        Some(mode) => {
+            let keys = match mode {
+                PipelineMode::Dictate => &combo,
+                PipelineMode::Rewrite => &rewrite_combo,
+            };
+            if !held(keys) {
+                *active = None;
+                drop(active);
+                on_hotkey_released(app, mode);
+            }
+        }
     }
 }
 
-fn on_hotkey_pressed(app: &AppHandle) {
+/// How often the input level is reported while recording.
+const LEVEL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long before the maximum recording length the warning tone plays, at
+/// most; short limits get a quarter of their length.
+const WARNING_LEAD: Duration = Duration::from_secs(10);
+
+fn on_hotkey_pressed(app: &AppHandle, mode: PipelineMode) {
     let app_state = app.state::<AppState>();
+    let run_id = uuid::Uuid::new_v4().to_string();
+    *app_state.hotkey_state.run_id.lock().unwrap() = run_id.clone();
     let settings = app_state.settings.lock().unwrap();
     let device_index = settings.audio_device_index;
+    let max_duration = settings.max_recording_duration();
+    let local_whisper = settings.preloads_local_model();
+    let wants_focus = mode == PipelineMode::Dictate
+        && (settings.llm.app_context || !settings.llm.app_rules.is_empty());
+    let llm_config = settings.llm.clone();
     drop(settings);
 
+    // Remember where the text is going before our overlay appears. Spawning a
+    // helper process takes a few milliseconds, so do it off this thread. A
+    // capture that finishes after the next run started is dropped.
+    *app_state.focused_app.lock().unwrap() = None;
+    if wants_focus {
+        let focused_app = Arc::clone(&app_state.focused_app);
+        let hotkey_state = Arc::clone(&app_state.hotkey_state);
+        let run_id = run_id.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            let Some(captured) = crate::focus::capture() else {
+                return;
+            };
+            let mut focused_app = focused_app.lock().unwrap();
+            if *hotkey_state.run_id.lock().unwrap() == run_id {
+                *focused_app = Some((run_id, captured));
+            }
+        });
+    }
+
+    // Reload an idle-unloaded model while the user is still speaking, so the
+    // reload doesn't add to transcription latency.
+    if local_whisper {
+        let engine = Arc::clone(&app_state.whisper);
+        tauri::async_runtime::spawn_blocking(move || {
+            if let Err(e) = engine.preload() {
+                eprintln!("Failed to pre-load whisper model: {}", e);
+            }
+        });
+    }
+
+    // Likewise get the cleanup model loaded while the user speaks. Skipped
+    // while the breaker has the endpoint marked as down.
+    if app_state.llm_breaker.allow() {
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = crate::llm::warm_up(&llm_config).await {
+                eprintln!("Failed to warm up LLM: {}", e);
+            }
+        });
+    }
+
     app_state.sound_player.play(sounds::START_TONE);
     tray::set_tray_status(app, "recording");
     tray::show_overlay(app);
@@ -123,67 +213,130 @@ fn on_hotkey_pressed(app: &AppHandle) {
         .recorder
         .lock()
         .unwrap()
-        .start_recording(device_index)
+        .start_recording(device_index, max_duration)
     {
         eprintln!("Failed to start recording: {}", e);
-        let _ = app.emit(
-            "pipeline-status",
-            PipelineStatusEvent {
-                status: PipelineStatus::Error,
-                raw_text: None,
-                cleaned_text: None,
-                error: Some(format!("Failed to start recording: {}", e)),
-            },
-        );
+        let _ = app.emit("pipeline-status", PipelineStatusEvent::failed(&run_id, &e));
         return;
     }
 
     let _ = app.emit(
         "pipeline-status",
-        PipelineStatusEvent {
-            status: PipelineStatus::Recording,
-            raw_text: None,
-            cleaned_text: None,
-            error: None,
-        },
+        PipelineStatusEvent::new(&run_id, PipelineStatus::Recording),
     );
-}
 
-fn on_hotkey_released(app: &AppHandle) {
-    let app_state = app.state::<AppState>();
-    app_state.sound_player.play(sounds::STOP_TONE);
-
-    let app_clone = app.clone();
+    // Report the input level until the combo is released or another run
+    // starts, and stop the recording at the maximum length.
+    let app = app.clone();
+    let started = Instant::now();
     tauri::async_runtime::spawn(async move {
-        if let Err(e) = pipeline::run_pipeline(app_clone.clone()).await {
-            eprintln!("Pipeline error: {}", e);
-            tray::set_tray_status(&app_clone, "idle");
-            tray::hide_overlay(&app_clone);
-            let _ = app_clone.emit(
+        let mut warned = false;
+        loop {
+            tokio::time::sleep(LEVEL_INTERVAL).await;
+            let app_state = app.state::<AppState>();
+            let hotkey_state = &app_state.hotkey_state;
+            if hotkey_state.active.lock().unwrap().is_none()
+                || *hotkey_state.run_id.lock().unwrap() != run_id
+            {
+                break;
+            }
+            if let Some(max) = max_duration {
+                let elapsed = started.elapsed();
+                if !warned && elapsed + WARNING_LEAD.min(max / 4) >= max {
+                    app_state.sound_player.play(sounds::WARNING_TONE);
+                    warned = true;
+                }
+                if elapsed >= max {
+                    cut_off(&app, &run_id);
+                    break;
+                }
+            }
+            let Some(level) = app_state.recorder.lock().unwrap().level() else {
+                break;
+            };
+            let _ = app.emit(
                 "pipeline-status",
                 PipelineStatusEvent {
-                    status: PipelineStatus::Error,
-                    raw_text: None,
-                    cleaned_text: None,
-                    error: Some(e.to_string()),
+                    level: Some(level),
+                    ..PipelineStatusEvent::new(&run_id, PipelineStatus::Recording)
                 },
             );
         }
     });
 }
 
+/// End the recording of `run_id` as if its combo had been released.
+fn cut_off(app: &AppHandle, run_id: &str) {
+    let hotkey_state = &app.state::<AppState>().hotkey_state;
+    let mut active = hotkey_state.active.lock().unwrap();
+    let Some(mode) = *active else {
+        return; // released in the meantime
+    };
+    if *hotkey_state.run_id.lock().unwrap() != run_id {
+        return;
+    }
+    eprintln!("Recording reached the maximum length, stopping");
+    *active = None;
+    hotkey_state.cut_off.store(true, Ordering::SeqCst);
+    drop(active);
+    on_hotkey_released(app, mode);
+}
+
+fn on_hotkey_released(app: &AppHandle, mode: PipelineMode) {
+    let app_state = app.state::<AppState>();
+    app_state.sound_player.play(sounds::STOP_TONE);
+    let run_id = app_state.hotkey_state.run_id.lock().unwrap().clone();
+
+    // Free the recorder right away so the next recording can start while
+    // this one waits in the queue. Encoding is left to the queued run: this
+    // runs on the key listener, which must not block.
+    let recording = match app_state.recorder.lock().unwrap().finish_recording() {
+        Ok(recording) => recording,
+        Err(e) => {
+            eprintln!("Failed to stop recording: {}", e);
+            let _ = app.emit("pipeline-status", PipelineStatusEvent::failed(&run_id, &e));
+            pipeline::reset_after_delay(app, Duration::ZERO);
+            return;
+        }
+    };
+
+    let focused_app = app_state
+        .focused_app
+        .lock()
+        .unwrap()
+        .take()
+        .filter(|(captured_for, _)| *captured_for == run_id)
+        .map(|(_, focused_app)| focused_app);
+    app_state.run_queue.push(
+        app,
+        QueuedRun {
+            run_id,
+            mode,
+            recording,
+            focused_app,
+        },
+    );
+}
+
 /// Update the hotkey combo at runtime (no need to re-register).
 pub fn update_hotkey(state: &Arc<HotkeyState>, hotkey_str: &str) {
     let new_combo = parse_hotkey_string(hotkey_str);
     *state.combo.lock().unwrap() = new_combo;
-    state.combo_active.store(false, Ordering::Relaxed);
+    *state.active.lock().unwrap() = None;
+}
+
+/// Update the rewrite-mode combo at runtime; an empty string disables it.
+pub fn update_rewrite_hotkey(state: &Arc<HotkeyState>, hotkey_str: &str) {
+    let new_combo = parse_hotkey_string(hotkey_str);
+    *state.rewrite_combo.lock().unwrap() = new_combo;
+    *state.active.lock().unwrap() = None;
 }
 
 /// Pause/unpause the listener (used during UI hotkey recording).
 pub fn set_paused(state: &Arc<HotkeyState>, paused: bool) {
     state.paused.store(paused, Ordering::Relaxed);
     if paused {
-        state.combo_active.store(false, Ordering::Relaxed);
+        *state.active.lock().unwrap() = None;
     }
 }
 
@@ -1,5 +1,9 @@
 mod audio;
 mod error;
+#[cfg(test)]
+mod eval;
+mod few_shot;
+mod focus;
 mod history;
 mod hotkey;
 #[cfg(target_os = "macos")]
@@ -9,8 +13,14 @@ mod macos_microphone;
 mod llm;
 mod output;
 mod pipeline;
+mod run_queue;
+mod secrets;
 mod settings;
 mod sounds;
+mod stages;
+#[cfg(test)]
+mod test_support;
+mod transcribe;
 mod tray;
 mod whisper;
 
@@ -18,6 +28,7 @@ use audio::AudioRecorder;
 use error::AppError;
 use history::HistoryDb;
 use hotkey::HotkeyState;
+use run_queue::RunQueue;
 use settings::AppSettings;
 use sounds::SoundPlayer;
 use std::sync::{Arc, Mutex};
@@ -27,11 +38,17 @@ use whisper::WhisperEngine;
 
 pub struct AppState {
     pub recorder: Mutex<AudioRecorder>,
-    pub whisper: WhisperEngine,
+    pub whisper: Arc<WhisperEngine>,
     pub settings: Mutex<AppSettings>,
+    pub llm_breaker: llm::CircuitBreaker,
     pub history: HistoryDb,
     pub sound_player: SoundPlayer,
     pub hotkey_state: Arc<HotkeyState>,
+    /// Window focused when the current recording started (see `focus`),
+    /// with the id of that recording's run.
+    pub focused_app: Arc<Mutex<Option<(String, focus::FocusedApp)>>>,
+    /// Finished hotkey recordings, processed one at a time.
+    pub run_queue: RunQueue,
 }
 
 // --- Audio commands ---
@@ -56,7 +73,12 @@ async fn start_recording(
 ) -> Result<(), AppError> {
     tauri::async_runtime::spawn_blocking(move || {
         let state = app.state::<AppState>();
-        let result = state.recorder.lock().unwrap().start_recording(device_index);
+        let max_duration = state.settings.lock().unwrap().max_recording_duration();
+        let result = state
+            .recorder
+            .lock()
+            .unwrap()
+            .start_recording(device_index, max_duration);
         result
     })
     .await
@@ -126,7 +148,46 @@ async fn cleanup_text(
 #[tauri::command]
 async fn test_llm_connection(state: tauri::State<'_, AppState>) -> Result<String, AppError> {
     let config = state.settings.lock().unwrap().llm.clone();
-    llm::test_connection(&config).await
+    let result = llm::test_connection(&config).await;
+    // A working endpoint ends any cooldown right away.
+    if result.is_ok() {
+        state.llm_breaker.record_success();
+        tray::set_llm_degraded(false);
+    }
+    result
+}
+
+#[tauri::command]
+async fn list_llm_models(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<llm::LlmModelInfo>, AppError> {
+    let config = state.settings.lock().unwrap().llm.clone();
+    llm::list_models(&config).await
+}
+
+#[tauri::command]
+async fn pull_llm_model(app: tauri::AppHandle, model: String) -> Result<(), AppError> {
+    use tauri::Emitter;
+    let config = app.state::<AppState>().settings.lock().unwrap().llm.clone();
+    llm::pull_model(&config, &model, |p| {
+        let progress = match (p.completed, p.total) {
+            (Some(completed), Some(total)) if total > 0 => {
+                (completed as f64 / total as f64 * 100.0) as u32
+            }
+            _ => 0,
+        };
+        let _ = app.emit(
+            "llm-model-pull-progress",
+            serde_json::json!({
+                "model": model,
+                "status": p.status,
+                "progress": progress,
+                "completed": p.completed,
+                "total": p.total,
+            }),
+        );
+    })
+    .await
 }
 
 // --- Output commands ---
@@ -278,29 +339,40 @@ fn save_settings(
     app: tauri::AppHandle,
     settings: AppSettings,
 ) -> Result<(), AppError> {
+    settings
+        .llm
+        .options
+        .validate(&settings.llm.api_type)
+        .map_err(AppError::Settings)?;
+
     let state = app.state::<AppState>();
 
     // Check if hotkey changed
     let old_hotkey = state.settings.lock().unwrap().hotkey.clone();
     let hotkey_changed = old_hotkey != settings.hotkey;
+    let old_rewrite_hotkey = state.settings.lock().unwrap().rewrite_hotkey.clone();
+    let rewrite_hotkey_changed = old_rewrite_hotkey != settings.rewrite_hotkey;
 
     // Check if whisper model changed
     let old_model = state.settings.lock().unwrap().whisper_model.clone();
     let model_changed = old_model != settings.whisper_model;
 
-    // Update in-memory settings
-    *state.settings.lock().unwrap() = settings.clone();
-
-    // Persist to store
+    // Persist to store, keeping only keyring references in memory
     let store = app
         .store("settings.json")
         .map_err(|e| AppError::Settings(e.to_string()))?;
-    settings::save_settings(&store, &settings)?;
+    let settings = settings::save_settings(&store, &settings)?;
+
+    // Update in-memory settings
+    *state.settings.lock().unwrap() = settings.clone();
 
     // Apply side effects
     if hotkey_changed {
         hotkey::update_hotkey(&state.hotkey_state, &settings.hotkey);
     }
+    if rewrite_hotkey_changed {
+        hotkey::update_rewrite_hotkey(&state.hotkey_state, &settings.rewrite_hotkey);
+    }
 
     if model_changed && settings.whisper_mode == settings::WhisperMode::Local {
         if let Err(e) = state.whisper.load_model(&settings.whisper_model) {
@@ -313,20 +385,91 @@ fn save_settings(
 
 #[tauri::command]
 fn reset_settings(app: tauri::AppHandle) -> Result<AppSettings, AppError> {
-    let defaults = AppSettings::default();
     let state = app.state::<AppState>();
-    *state.settings.lock().unwrap() = defaults.clone();
-
     let store = app
         .store("settings.json")
         .map_err(|e| AppError::Settings(e.to_string()))?;
-    settings::save_settings(&store, &defaults)?;
+    let defaults = settings::save_settings(&store, &AppSettings::default())?;
+    *state.settings.lock().unwrap() = defaults.clone();
 
     hotkey::update_hotkey(&state.hotkey_state, &defaults.hotkey);
+    hotkey::update_rewrite_hotkey(&state.hotkey_state, &defaults.rewrite_hotkey);
 
     Ok(defaults)
 }
 
+// --- Few-shot example commands ---
+
+/// Apply `update` to the in-memory settings and persist the result.
+fn update_settings(
+    app: &tauri::AppHandle,
+    update: impl FnOnce(&mut AppSettings),
+) -> Result<AppSettings, AppError> {
+    let state = app.state::<AppState>();
+    let settings = {
+        let mut settings = state.settings.lock().unwrap();
+        update(&mut settings);
+        settings.clone()
+    };
+
+    let store = app
+        .store("settings.json")
+        .map_err(|e| AppError::Settings(e.to_string()))?;
+    let settings = settings::save_settings(&store, &settings)?;
+    *state.settings.lock().unwrap() = settings.clone();
+    Ok(settings)
+}
+
+#[tauri::command]
+fn export_few_shot_examples(
+    state: tauri::State<'_, AppState>,
+    format: few_shot::ExampleSetFormat,
+) -> Result<String, AppError> {
+    let examples = state.settings.lock().unwrap().llm.few_shot_examples.clone();
+    few_shot::export(&examples, format)
+}
+
+#[tauri::command]
+fn import_few_shot_examples(
+    app: tauri::AppHandle,
+    contents: String,
+    format: few_shot::ExampleSetFormat,
+    replace: bool,
+) -> Result<AppSettings, AppError> {
+    let imported = few_shot::import(&contents, format)?;
+    update_settings(&app, |settings| {
+        if replace {
+            settings.llm.few_shot_examples.clear();
+        }
+        few_shot::merge(&mut settings.llm.few_shot_examples, imported);
+    })
+}
+
+/// Turn a history item into a few-shot example: its raw transcription becomes
+/// the input and `cleaned_text` (the user's correction, or the stored cleaned
+/// text if omitted) the expected output.
+#[tauri::command]
+fn promote_history_item(
+    app: tauri::AppHandle,
+    id: String,
+    cleaned_text: Option<String>,
+) -> Result<AppSettings, AppError> {
+    let record = app.state::<AppState>().history.get(&id)?;
+    let output = cleaned_text.unwrap_or(record.cleaned_text);
+    if record.raw_text.trim().is_empty() || output.trim().is_empty() {
+        return Err(AppError::Settings(
+            "Examples need a non-empty input and output".into(),
+        ));
+    }
+    let example = llm::FewShotExample {
+        input: record.raw_text,
+        output,
+    };
+    update_settings(&app, |settings| {
+        few_shot::merge(&mut settings.llm.few_shot_examples, vec![example]);
+    })
+}
+
 // --- History commands ---
 
 #[tauri::command]
@@ -339,6 +482,12 @@ fn delete_history_item(state: tauri::State<'_, AppState>, id: String) -> Result<
     state.history.delete(&id)
 }
 
+/// p50/p95 latency per pipeline step and model, over the stored history.
+#[tauri::command]
+fn get_latency_stats(state: tauri::State<'_, AppState>) -> Result<Vec<history::LatencyStats>, AppError> {
+    Ok(history::latency_stats(&state.history.list()?))
+}
+
 #[tauri::command]
 fn clear_history(state: tauri::State<'_, AppState>) -> Result<(), AppError> {
     state.history.clear_all()
@@ -347,10 +496,35 @@ fn clear_history(state: tauri::State<'_, AppState>) -> Result<(), AppError> {
 // --- Whisper API command ---
 
 #[tauri::command]
-async fn test_whisper_api(state: tauri::State<'_, AppState>) -> Result<String, AppError> {
+async fn test_whisper_api(
+    state: tauri::State<'_, AppState>,
+) -> Result<whisper::WhisperApiProbe, AppError> {
     let settings = state.settings.lock().unwrap().clone();
-    // Send a tiny silent WAV to test the endpoint
-    Ok(format!("Whisper API endpoint: {}", settings.whisper_api_endpoint))
+    if settings.whisper_api_endpoint.trim().is_empty() {
+        return Err(AppError::Whisper("No API endpoint configured".into()));
+    }
+    let config = whisper::WhisperApiConfig::from_settings(&settings);
+    Ok(whisper::probe_api(&config, &settings.whisper_language).await)
+}
+
+/// Periodically unload the local whisper model once it has been idle longer
+/// than `whisper_idle_unload_mins`. The engine reloads it on demand.
+fn spawn_whisper_idle_unloader(app: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+            let state = app.state::<AppState>();
+            let idle_mins = state.settings.lock().unwrap().whisper_idle_unload_mins;
+            if idle_mins == 0 {
+                continue;
+            }
+            let engine = Arc::clone(&state.whisper);
+            let _ = tauri::async_runtime::spawn_blocking(move || {
+                engine.unload_if_idle(std::time::Duration::from_secs(idle_mins * 60))
+            })
+            .await;
+        }
+    });
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -382,16 +556,17 @@ pub fn run() {
             let loaded_settings = settings::load_settings(&store);
 
             // Try to load whisper model if configured
-            let whisper_engine = WhisperEngine::new();
+            let whisper_engine = Arc::new(WhisperEngine::new());
             if loaded_settings.whisper_mode == settings::WhisperMode::Local {
                 if let Err(e) = whisper_engine.load_model(&loaded_settings.whisper_model) {
                     eprintln!("Could not load whisper model on startup: {}", e);
                 }
             }
 
-            let hotkey_state = Arc::new(HotkeyState::new(hotkey::parse_hotkey_string(
-                &loaded_settings.hotkey,
-            )));
+            let hotkey_state = Arc::new(HotkeyState::new(
+                hotkey::parse_hotkey_string(&loaded_settings.hotkey),
+                hotkey::parse_hotkey_string(&loaded_settings.rewrite_hotkey),
+            ));
 
             // On macOS the global hotkey + auto-paste need Accessibility permission.
             // Only start the listener when granted (a tap created without the grant
@@ -431,11 +606,16 @@ pub fn run() {
                 recorder: Mutex::new(AudioRecorder::new()),
                 whisper: whisper_engine,
                 settings: Mutex::new(loaded_settings.clone()),
+                llm_breaker: llm::CircuitBreaker::new(),
                 history: history_db,
                 sound_player: SoundPlayer::new(),
                 hotkey_state,
+                focused_app: Arc::new(Mutex::new(None)),
+                run_queue: RunQueue::start(app.handle().clone()),
             });
 
+            spawn_whisper_idle_unloader(app.handle().clone());
+
             if let Some(window) = app.get_webview_window("main") {
                 let _ = window.show();
                 let _ = window.set_focus();
@@ -454,6 +634,8 @@ pub fn run() {
             is_whisper_model_loaded,
             cleanup_text,
             test_llm_connection,
+            list_llm_models,
+            pull_llm_model,
             copy_to_clipboard,
             paste_text,
             set_hotkey,
@@ -472,6 +654,10 @@ pub fn run() {
             get_history,
             delete_history_item,
             clear_history,
+            get_latency_stats,
+            export_few_shot_examples,
+            import_few_shot_examples,
+            promote_history_item,
             test_whisper_api,
         ])
         .run(tauri::generate_context!())
@@ -23,7 +23,7 @@ pub fn copy_and_paste(
     if auto_paste {
         // Small delay to ensure clipboard is ready
         std::thread::sleep(std::time::Duration::from_millis(100));
-        if let Err(e) = simulate_paste(app, paste_shortcut) {
+        if let Err(e) = simulate_shortcut(app, paste_shortcut) {
             eprintln!("Auto-paste failed (text is in clipboard): {}", e);
         }
     }
@@ -31,11 +31,77 @@ pub fn copy_and_paste(
     Ok(())
 }
 
-/// Parse a shortcut string like "Ctrl+Shift+V" or "Cmd+V" into modifier keys + a character.
-fn parse_paste_shortcut(shortcut: &str) -> Result<(Vec<Key>, Key), AppError> {
+/// Copy the focused application's selection by sending `copy_shortcut`, and
+/// return it, or `None` if nothing was selected. The clipboard is cleared
+/// first so that "nothing selected" isn't mistaken for whatever was on it
+/// before, and the old contents are put back either way.
+pub fn copy_selection(app: &tauri::AppHandle, copy_shortcut: &str) -> Result<Option<String>, AppError> {
+    let previous = app.clipboard().read_text().ok();
+    app.clipboard()
+        .write_text("")
+        .map_err(|e| AppError::Output(format!("Clipboard write failed: {}", e)))?;
+
+    let selection = wait_for_selection(app, copy_shortcut);
+
+    if let Some(previous) = previous {
+        let _ = app.clipboard().write_text(previous);
+    }
+    selection
+}
+
+fn wait_for_selection(app: &tauri::AppHandle, copy_shortcut: &str) -> Result<Option<String>, AppError> {
+    simulate_shortcut(app, copy_shortcut)?;
+
+    // The target app fills the clipboard asynchronously.
+    for _ in 0..20 {
+        std::thread::sleep(std::time::Duration::from_millis(25));
+        if let Ok(text) = app.clipboard().read_text() {
+            if !text.is_empty() {
+                return Ok(Some(text));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Type `text` into the focused application as keystrokes, without touching
+/// the clipboard. Used to insert LLM output progressively while it streams.
+pub fn type_text(app: &tauri::AppHandle, text: &str) -> Result<(), AppError> {
+    let text = text.to_string();
+
+    // Same main-thread requirement as the paste chord; see `simulate_shortcut`.
+    #[cfg(target_os = "macos")]
+    {
+        let (tx, rx) = std::sync::mpsc::channel();
+        app.run_on_main_thread(move || {
+            let _ = tx.send(type_chars(&text));
+        })
+        .map_err(|e| AppError::Output(format!("Failed to dispatch typing to main thread: {}", e)))?;
+        rx.recv_timeout(std::time::Duration::from_secs(5))
+            .map_err(|e| AppError::Output(format!("Typing task did not complete: {}", e)))?
+            .map_err(AppError::Output)
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = app;
+        type_chars(&text).map_err(AppError::Output)
+    }
+}
+
+fn type_chars(text: &str) -> Result<(), String> {
+    let mut enigo =
+        Enigo::new(&Settings::default()).map_err(|e| format!("Failed to create enigo: {}", e))?;
+    enigo
+        .text(text)
+        .map_err(|e| format!("Typing failed: {}", e))
+}
+
+/// Parse a shortcut string like "Ctrl+Shift+V" or "Cmd+C" into modifier keys + a character.
+fn parse_shortcut(shortcut: &str) -> Result<(Vec<Key>, Key), AppError> {
     let parts: Vec<&str> = shortcut.split('+').map(|s| s.trim()).collect();
     if parts.is_empty() {
-        return Err(AppError::Output("Empty paste shortcut".into()));
+        return Err(AppError::Output("Empty shortcut".into()));
     }
 
     let mut modifiers = Vec::new();
@@ -47,7 +113,7 @@ fn parse_paste_shortcut(shortcut: &str) -> Result<(Vec<Key>, Key), AppError> {
             "cmd" | "meta" | "super" => Key::Meta,
             other => {
                 return Err(AppError::Output(format!(
-                    "Unknown modifier in paste shortcut: {}",
+                    "Unknown modifier in shortcut: {}",
                     other
                 )))
             }
@@ -60,7 +126,7 @@ fn parse_paste_shortcut(shortcut: &str) -> Result<(Vec<Key>, Key), AppError> {
         Key::Unicode(last.to_lowercase().chars().next().unwrap())
     } else {
         return Err(AppError::Output(format!(
-            "Invalid key in paste shortcut: {}",
+            "Invalid key in shortcut: {}",
             last
         )));
     };
@@ -68,8 +134,9 @@ fn parse_paste_shortcut(shortcut: &str) -> Result<(Vec<Key>, Key), AppError> {
     Ok((modifiers, char_key))
 }
 
-fn simulate_paste(app: &tauri::AppHandle, paste_shortcut: &str) -> Result<(), AppError> {
-    let (modifiers, char_key) = parse_paste_shortcut(paste_shortcut)?;
+/// Synthesize a keyboard shortcut (the paste or copy chord) in the focused app.
+fn simulate_shortcut(app: &tauri::AppHandle, shortcut: &str) -> Result<(), AppError> {
+    let (modifiers, char_key) = parse_shortcut(shortcut)?;
 
     // On macOS 26.3+, enigo's character-key path calls TSMGetInputSourceProperty
     // (Text Services Manager) to resolve the layout-dependent keycode. TSM
@@ -81,26 +148,26 @@ fn simulate_paste(app: &tauri::AppHandle, paste_shortcut: &str) -> Result<(), Ap
     {
         let (tx, rx) = std::sync::mpsc::channel();
         app.run_on_main_thread(move || {
-            let _ = tx.send(press_paste_chord(&modifiers, char_key));
+            let _ = tx.send(press_chord(&modifiers, char_key));
         })
         .map_err(|e| {
-            AppError::Output(format!("Failed to dispatch paste to main thread: {}", e))
+            AppError::Output(format!("Failed to dispatch shortcut to main thread: {}", e))
         })?;
         rx.recv_timeout(std::time::Duration::from_secs(5))
-            .map_err(|e| AppError::Output(format!("Paste task did not complete: {}", e)))?
+            .map_err(|e| AppError::Output(format!("Shortcut task did not complete: {}", e)))?
             .map_err(AppError::Output)
     }
 
     #[cfg(not(target_os = "macos"))]
     {
         let _ = app;
-        press_paste_chord(&modifiers, char_key).map_err(AppError::Output)
+        press_chord(&modifiers, char_key).map_err(AppError::Output)
     }
 }
 
-/// Synthesize the paste chord (modifiers + key). On macOS this MUST run on the
-/// main thread — see the note in `simulate_paste`.
-fn press_paste_chord(modifiers: &[Key], char_key: Key) -> Result<(), String> {
+/// Synthesize a chord (modifiers + key). On macOS this MUST run on the main
+/// thread — see the note in `simulate_shortcut`.
+fn press_chord(modifiers: &[Key], char_key: Key) -> Result<(), String> {
     let mut enigo =
         Enigo::new(&Settings::default()).map_err(|e| format!("Failed to create enigo: {}", e))?;
 
@@ -1,144 +1,538 @@
+use futures::future::BoxFuture;
 use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::Instant;
 use tauri::{AppHandle, Emitter, Manager};
 
+use crate::audio::FinishedRecording;
 use crate::error::AppError;
 use crate::history::TranscriptionRecord;
-use crate::settings::WhisperMode;
+use crate::llm::LlmConfig;
+use crate::run_queue::QueuedRun;
+use crate::settings::AppSettings;
+use crate::stages::{
+    self, FilterStage, ProgressiveTyper, ReplaceStage, ScriptStage, Stage, StageConfig, StageContext, StageKind,
+    StageOutcome,
+};
+use crate::transcribe;
 use crate::tray;
 use crate::AppState;
 
+/// What a hotkey press does with the recording.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PipelineMode {
+    /// Transcribe, clean up and paste (`run_pipeline`).
+    Dictate,
+    /// Treat the utterance as an instruction for the selected text
+    /// (`run_rewrite_pipeline`).
+    Rewrite,
+}
+
 #[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum PipelineStatus {
     Recording,
+    /// Recorded, waiting for earlier runs to finish.
+    Queued,
     Transcribing,
     Cleaning,
     Done,
     Error,
 }
 
+/// Progress of a run, emitted as `pipeline-status`. Every event from
+/// `Recording` to `Done` or `Error` carries the run's `run_id`, so listeners
+/// can tell the events of back-to-back runs apart.
 #[derive(Debug, Clone, Serialize)]
 pub struct PipelineStatusEvent {
+    pub run_id: String,
     pub status: PipelineStatus,
+    /// Pipeline profile of a dictation.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// Transcription backend, once the audio is transcribed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backend: Option<String>,
+    /// Milliseconds per finished step, as in `TranscriptionRecord::timings`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub timings: BTreeMap<String, u64>,
+    /// Input level while recording (RMS, 0 to 1).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub raw_text: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cleaned_text: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+    /// `AppError::code` of the failure, or "no_speech" / "dropped" when the
+    /// run ended with nothing to output.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<&'static str>,
+    /// Something went wrong that the run recovers from; unlike `error`, the
+    /// run goes on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+}
+
+impl PipelineStatusEvent {
+    pub fn new(run_id: &str, status: PipelineStatus) -> Self {
+        Self {
+            run_id: run_id.to_string(),
+            status,
+            profile: None,
+            backend: None,
+            timings: BTreeMap::new(),
+            level: None,
+            raw_text: None,
+            cleaned_text: None,
+            error: None,
+            error_code: None,
+            warning: None,
+        }
+    }
+
+    pub fn failed(run_id: &str, error: &AppError) -> Self {
+        Self {
+            error: Some(error.to_string()),
+            error_code: Some(error.code()),
+            ..Self::new(run_id, PipelineStatus::Error)
+        }
+    }
+
+    /// An event for the dictation in `ctx`, with its profile, backend and
+    /// timings so far.
+    fn for_dictation(ctx: &StageContext, status: PipelineStatus) -> Self {
+        Self {
+            profile: Some(ctx.profile.clone()),
+            backend: Some(ctx.backend.clone()),
+            timings: ctx.timings.clone(),
+            ..Self::new(&ctx.run_id, status)
+        }
+    }
 }
 
 fn emit_status(app: &AppHandle, event: &PipelineStatusEvent) {
     let _ = app.emit("pipeline-status", event);
 }
 
-pub async fn run_pipeline(app: AppHandle) -> Result<(), AppError> {
-    let start_time = std::time::Instant::now();
+/// Reset the tray and hide the overlay after `delay`, unless by then another
+/// run is recording or queued.
+pub fn reset_after_delay(app: &AppHandle, delay: std::time::Duration) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(delay).await;
+        let state = app.state::<AppState>();
+        if state.hotkey_state.is_recording() || state.run_queue.depth() > 0 {
+            return;
+        }
+        tray::set_tray_status(&app, "idle");
+        tray::hide_overlay(&app);
+    });
+}
+
+/// Show a run's progress in the tray, unless the next recording already
+/// took it over.
+fn set_tray_status(app: &AppHandle, status: &str) {
+    if !app.state::<AppState>().hotkey_state.is_recording() {
+        tray::set_tray_status(app, status);
+    }
+}
+
+fn elapsed_ms(since: Instant) -> u64 {
+    since.elapsed().as_millis() as u64
+}
+
+/// Encode a queued recording, off the async runtime.
+async fn encode_recording(recording: FinishedRecording) -> Result<Vec<u8>, AppError> {
+    tauri::async_runtime::spawn_blocking(move || recording.into_wav())
+        .await
+        .map_err(|e| AppError::Audio(format!("task join error: {e}")))?
+}
+
+pub async fn run_pipeline(app: AppHandle, run: QueuedRun) -> Result<(), AppError> {
+    let run_id = run.run_id;
+    let state = app.state::<AppState>();
+    let settings = state.settings.lock().unwrap().clone();
+    let profile = stages::active_profile(&settings.pipeline_profiles, &settings.active_profile);
 
-    // 1. Stop recording and get WAV bytes
-    tray::set_tray_status(&app, "processing");
+    set_tray_status(&app, "processing");
     emit_status(
         &app,
         &PipelineStatusEvent {
-            status: PipelineStatus::Transcribing,
-            raw_text: None,
-            cleaned_text: None,
-            error: None,
+            profile: Some(profile.name.clone()),
+            ..PipelineStatusEvent::new(&run_id, PipelineStatus::Transcribing)
         },
     );
 
-    let state = app.state::<AppState>();
-
-    let wav_bytes = state.recorder.lock().unwrap().stop_recording()?;
-    let duration_secs = start_time.elapsed().as_secs_f64();
+    let stop_start = Instant::now();
+    let wav_bytes = encode_recording(run.recording).await?;
+    let mut timings = BTreeMap::new();
+    timings.insert("stop".to_string(), elapsed_ms(stop_start));
 
-    // 2. Transcribe
-    let settings = state.settings.lock().unwrap().clone();
+    // 1. Transcribe
+    let transcribe_start = Instant::now();
+    let chain = transcribe::chain_from_settings(&settings, &state.whisper);
+    let transcription =
+        transcribe::transcribe_with_fallback(&chain, &wav_bytes, &settings.whisper_language).await?;
+    timings.insert("transcribe".to_string(), elapsed_ms(transcribe_start));
+    let Some(transcription) = transcription else {
+        let event = PipelineStatusEvent {
+            profile: Some(profile.name.clone()),
+            timings,
+            ..PipelineStatusEvent::new(&run_id, PipelineStatus::Error)
+        };
+        stop_with_error(&app, event, "no_speech", "No speech detected".into());
+        return Ok(());
+    };
 
-    let raw_text = match settings.whisper_mode {
-        WhisperMode::Local => state.whisper.transcribe(&wav_bytes, &settings.whisper_language)?,
-        WhisperMode::Api => {
-            crate::whisper::transcribe_via_api(
-                &settings.whisper_api_endpoint,
-                &settings.whisper_api_key,
-                &settings.whisper_api_model,
-                &wav_bytes,
-                &settings.whisper_language,
-            )
-            .await?
-        }
+    // 2. Run the active profile's stages (by default: cleanup, output, history)
+    let stages: Vec<Box<dyn Stage>> = profile
+        .stages
+        .iter()
+        .map(|config| build_stage(&app, &settings, config))
+        .collect();
+    let mut ctx = StageContext {
+        run_id,
+        profile: profile.name.clone(),
+        raw_text: transcription.text.clone(),
+        text: transcription.text,
+        backend: transcription.backend,
+        duration_secs: crate::whisper::wav_duration_secs(&wav_bytes),
+        focused_app: run.focused_app,
+        timings,
+        ..StageContext::default()
     };
 
-    if raw_text.trim().is_empty() {
-        tray::set_tray_status(&app, "idle");
-        tray::hide_overlay(&app);
-        emit_status(
-            &app,
-            &PipelineStatusEvent {
-                status: PipelineStatus::Error,
-                raw_text: None,
-                cleaned_text: None,
-                error: Some("No speech detected".into()),
-            },
-        );
+    if let StageOutcome::Stop(reason) = stages::run_stages(&stages, &mut ctx).await? {
+        let event = PipelineStatusEvent::for_dictation(&ctx, PipelineStatus::Error);
+        stop_with_error(&app, event, "dropped", format!("Dropped: {}", reason));
         return Ok(());
     }
 
+    set_tray_status(&app, "done");
     emit_status(
         &app,
         &PipelineStatusEvent {
-            status: PipelineStatus::Cleaning,
-            raw_text: Some(raw_text.clone()),
-            cleaned_text: None,
-            error: None,
+            raw_text: Some(ctx.raw_text.clone()),
+            cleaned_text: Some(ctx.text.clone()),
+            ..PipelineStatusEvent::for_dictation(&ctx, PipelineStatus::Done)
         },
     );
 
-    // 3. LLM cleanup (graceful degradation: skip if unavailable)
-    let cleaned_text = match crate::llm::cleanup_text(&settings.llm, &raw_text).await {
-        Ok(cleaned) => cleaned,
-        Err(e) => {
-            eprintln!("LLM cleanup failed, using raw text: {}", e);
-            raw_text.clone()
-        }
-    };
-
-    // 4. Output
-    crate::output::copy_and_paste(&app, &cleaned_text, settings.auto_paste, &settings.paste_shortcut)?;
+    Ok(())
+}
 
-    tray::set_tray_status(&app, "done");
+/// End a dictation that produced nothing to output.
+fn stop_with_error(app: &AppHandle, event: PipelineStatusEvent, code: &'static str, error: String) {
     emit_status(
-        &app,
+        app,
         &PipelineStatusEvent {
-            status: PipelineStatus::Done,
-            raw_text: Some(raw_text.clone()),
-            cleaned_text: Some(cleaned_text.clone()),
-            error: None,
+            status: PipelineStatus::Error,
+            error: Some(error),
+            error_code: Some(code),
+            ..event
         },
     );
+}
 
-    // Reset tray and hide overlay after 2 seconds
-    let app_for_reset = app.clone();
-    tauri::async_runtime::spawn(async move {
-        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
-        tray::set_tray_status(&app_for_reset, "idle");
-        tray::hide_overlay(&app_for_reset);
-    });
+fn build_stage(app: &AppHandle, settings: &AppSettings, config: &StageConfig) -> Box<dyn Stage> {
+    match config {
+        StageConfig::Cleanup => Box::new(CleanupStage {
+            app: app.clone(),
+            llm: settings.llm.clone(),
+            progressive: settings.auto_paste
+                && settings.progressive_paste
+                && settings.llm.stream
+                && !settings.llm.faithfulness_check,
+        }),
+        StageConfig::Replace { rules } => Box::new(ReplaceStage {
+            rules: rules.clone(),
+        }),
+        StageConfig::Filter { min_words, ignore } => Box::new(FilterStage {
+            min_words: *min_words,
+            ignore: ignore.clone(),
+        }),
+        StageConfig::Script {
+            command,
+            args,
+            timeout_secs,
+        } => Box::new(ScriptStage {
+            command: command.clone(),
+            args: args.clone(),
+            timeout: std::time::Duration::from_secs(*timeout_secs),
+        }),
+        StageConfig::Output => Box::new(OutputStage {
+            app: app.clone(),
+            auto_paste: settings.auto_paste,
+            paste_shortcut: settings.paste_shortcut.clone(),
+        }),
+        StageConfig::History => Box::new(HistoryStage {
+            app: app.clone(),
+            max_items: settings.history_max_items,
+        }),
+    }
+}
+
+/// LLM cleanup (graceful degradation: the text is left as is if the LLM is
+/// unavailable). While the response streams in, it is shown in the overlay
+/// and, with progressive paste, typed straight into the target app.
+struct CleanupStage {
+    app: AppHandle,
+    llm: LlmConfig,
+    progressive: bool,
+}
+
+impl Stage for CleanupStage {
+    fn name(&self) -> &'static str {
+        "cleanup"
+    }
+
+    fn kind(&self) -> StageKind {
+        StageKind::Transform
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut StageContext) -> BoxFuture<'a, Result<StageOutcome, AppError>> {
+        Box::pin(async move {
+            let app = &self.app;
+            let state = app.state::<AppState>();
+            let input = ctx.text.clone();
+            let progress = PipelineStatusEvent {
+                raw_text: Some(ctx.raw_text.clone()),
+                ..PipelineStatusEvent::for_dictation(ctx, PipelineStatus::Cleaning)
+            };
+            emit_status(app, &progress);
+
+            let progressive = self.progressive && ctx.may_type;
+            let mut streamed = String::new();
+            let mut typer = ProgressiveTyper::new(|piece: &str| crate::output::type_text(app, piece));
+            let on_text = |piece: &str| {
+                streamed.push_str(piece);
+                emit_status(
+                    app,
+                    &PipelineStatusEvent {
+                        cleaned_text: Some(streamed.clone()),
+                        ..progress.clone()
+                    },
+                );
+                if progressive {
+                    typer.push(piece);
+                }
+            };
+            // After repeated failures the breaker skips cleanup for a while
+            // rather than paying the timeout on every dictation.
+            let cleanup = if state.llm_breaker.allow() {
+                ctx.llm_model = self.llm.model.clone();
+                let result = crate::llm::cleanup_text_streaming(
+                    &self.llm,
+                    &input,
+                    ctx.focused_app.as_ref(),
+                    on_text,
+                )
+                .await;
+                match &result {
+                    Ok(_) => state.llm_breaker.record_success(),
+                    Err(_) => state.llm_breaker.record_failure(
+                        self.llm.breaker_threshold,
+                        std::time::Duration::from_secs(self.llm.breaker_cooldown_secs),
+                    ),
+                }
+                result
+            } else {
+                Err(AppError::Llm("skipped after repeated failures".into()))
+            };
+            tray::set_llm_degraded(state.llm_breaker.is_open());
 
-    // 5. Save to history
-    let record = TranscriptionRecord {
-        id: uuid::Uuid::new_v4().to_string(),
-        raw_text,
-        cleaned_text,
-        created_at: chrono::Utc::now().to_rfc3339(),
-        duration_secs,
-        model_used: settings.whisper_model.clone(),
+            match cleanup {
+                Ok(cleaned) => ctx.text = cleaned,
+                Err(e) => eprintln!("LLM cleanup failed, using raw text: {}", e),
+            }
+            ctx.typed = typer.into_typed();
+            // Part of a text that was then replaced is already in the target
+            // app. The output sink won't paste over it; say where the text is.
+            if ctx.untyped_rest().is_none() {
+                emit_status(
+                    app,
+                    &PipelineStatusEvent {
+                        warning: Some(
+                            "Cleanup was interrupted after part of it was typed; the full text is on the clipboard"
+                                .to_string(),
+                        ),
+                        ..PipelineStatusEvent::for_dictation(ctx, PipelineStatus::Cleaning)
+                    },
+                );
+            }
+            Ok(StageOutcome::Continue)
+        })
+    }
+}
+
+/// Copy to the clipboard and paste. Text that was already typed progressively
+/// is not pasted a second time: if typing stopped partway only the rest is
+/// pasted, and if what was typed doesn't match the final text nothing is. The
+/// clipboard gets the final text unless part of it was pasted.
+struct OutputStage {
+    app: AppHandle,
+    auto_paste: bool,
+    paste_shortcut: String,
+}
+
+impl Stage for OutputStage {
+    fn name(&self) -> &'static str {
+        stages::OUTPUT_STAGE
+    }
+
+    fn kind(&self) -> StageKind {
+        StageKind::Sink
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut StageContext) -> BoxFuture<'a, Result<StageOutcome, AppError>> {
+        Box::pin(async move {
+            match ctx.untyped_rest() {
+                // Nothing typed yet, or typing stopped partway.
+                Some(rest) if ctx.typed.is_empty() || !rest.is_empty() => {
+                    crate::output::copy_and_paste(&self.app, rest, self.auto_paste, &self.paste_shortcut)?
+                }
+                // All of it typed, or typed text that doesn't match.
+                _ => crate::output::copy_to_clipboard(&self.app, &ctx.text)?,
+            }
+            Ok(StageOutcome::Continue)
+        })
+    }
+}
+
+struct HistoryStage {
+    app: AppHandle,
+    max_items: usize,
+}
+
+impl Stage for HistoryStage {
+    fn name(&self) -> &'static str {
+        "history"
+    }
+
+    fn kind(&self) -> StageKind {
+        StageKind::Sink
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut StageContext) -> BoxFuture<'a, Result<StageOutcome, AppError>> {
+        Box::pin(async move {
+            let state = self.app.state::<AppState>();
+            let mut timings = ctx.timings.clone();
+            timings.insert("total".to_string(), ctx.timings.values().sum());
+            let record = TranscriptionRecord {
+                id: ctx.run_id.clone(),
+                raw_text: ctx.raw_text.clone(),
+                cleaned_text: ctx.text.clone(),
+                created_at: chrono::Utc::now().to_rfc3339(),
+                duration_secs: ctx.duration_secs,
+                model_used: ctx.backend.clone(),
+                llm_model: ctx.llm_model.clone(),
+                timings,
+            };
+
+            if let Err(e) = state.history.insert(&record) {
+                eprintln!("Failed to save history: {}", e);
+            }
+            let _ = state.history.prune(self.max_items);
+            Ok(StageOutcome::Continue)
+        })
+    }
+}
+
+/// Rewrite mode: copy the selection in the focused app, transcribe the
+/// utterance as an instruction, have the LLM apply it and paste the result
+/// over the selection. Failures leave the selection untouched: there is no
+/// sensible fallback text to paste.
+pub async fn run_rewrite_pipeline(app: AppHandle, run: QueuedRun) -> Result<(), AppError> {
+    let run_id = run.run_id;
+    set_tray_status(&app, "processing");
+    emit_status(&app, &PipelineStatusEvent::new(&run_id, PipelineStatus::Transcribing));
+
+    let state = app.state::<AppState>();
+
+    let stop_start = Instant::now();
+    let wav_bytes = encode_recording(run.recording).await?;
+    let mut timings = BTreeMap::new();
+    timings.insert("stop".to_string(), elapsed_ms(stop_start));
+    let settings = state.settings.lock().unwrap().clone();
+
+    // 1. Copy the selection. This happens once the run starts rather than on
+    // press: while the hotkey is held, its modifiers would combine with the
+    // copy chord (Ctrl+Shift+C is not copy in most apps). Behind queued runs,
+    // this is after their output.
+    let app_for_copy = app.clone();
+    let copy_shortcut = settings.copy_shortcut.clone();
+    let selection = tauri::async_runtime::spawn_blocking(move || {
+        crate::output::copy_selection(&app_for_copy, &copy_shortcut)
+    })
+    .await
+    .map_err(|e| AppError::Output(format!("task join error: {e}")))??
+    .ok_or_else(|| AppError::Output("Nothing selected to rewrite".into()))?;
+
+    // 2. Transcribe the instruction
+    let transcribe_start = Instant::now();
+    let chain = transcribe::chain_from_settings(&settings, &state.whisper);
+    let transcription = transcribe::transcribe_with_fallback(&chain, &wav_bytes, &settings.whisper_language)
+        .await?
+        .ok_or_else(|| AppError::Whisper("No speech detected".into()))?;
+    timings.insert("transcribe".to_string(), elapsed_ms(transcribe_start));
+    let instruction = transcription.text;
+
+    let progress = PipelineStatusEvent {
+        backend: Some(transcription.backend),
+        timings: timings.clone(),
+        raw_text: Some(instruction.clone()),
+        ..PipelineStatusEvent::new(&run_id, PipelineStatus::Cleaning)
     };
+    emit_status(&app, &progress);
 
-    if let Err(e) = state.history.insert(&record) {
-        eprintln!("Failed to save history: {}", e);
+    // 3. Rewrite, showing the result in the overlay as it streams
+    if !state.llm_breaker.allow() {
+        return Err(AppError::Llm("skipped after repeated failures".into()));
     }
-    let _ = state.history.prune(settings.history_max_items);
+    let rewrite_start = Instant::now();
+    let mut streamed = String::new();
+    let on_text = |piece: &str| {
+        streamed.push_str(piece);
+        emit_status(
+            &app,
+            &PipelineStatusEvent {
+                cleaned_text: Some(streamed.clone()),
+                ..progress.clone()
+            },
+        );
+    };
+    let result =
+        crate::llm::rewrite_text_streaming(&settings.llm, &selection, &instruction, on_text).await;
+    match &result {
+        Ok(_) => state.llm_breaker.record_success(),
+        Err(_) => state.llm_breaker.record_failure(
+            settings.llm.breaker_threshold,
+            std::time::Duration::from_secs(settings.llm.breaker_cooldown_secs),
+        ),
+    }
+    tray::set_llm_degraded(state.llm_breaker.is_open());
+    let rewritten = result?;
+    timings.insert("rewrite".to_string(), elapsed_ms(rewrite_start));
+
+    // 4. Paste over the selection, which is still active in the target app
+    let output_start = Instant::now();
+    crate::output::copy_and_paste(&app, &rewritten, settings.auto_paste, &settings.paste_shortcut)?;
+    timings.insert("output".to_string(), elapsed_ms(output_start));
+
+    set_tray_status(&app, "done");
+    emit_status(
+        &app,
+        &PipelineStatusEvent {
+            status: PipelineStatus::Done,
+            timings,
+            cleaned_text: Some(rewritten),
+            ..progress
+        },
+    );
 
+    // Not saved to history: it holds dictations, and a rewrite's instruction
+    // and result don't form a raw/cleaned pair.
     Ok(())
 }
@@ -0,0 +1,204 @@
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use futures::FutureExt;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::mpsc;
+
+use crate::audio::FinishedRecording;
+use crate::error::AppError;
+use crate::focus::FocusedApp;
+use crate::pipeline::{self, PipelineMode, PipelineStatus, PipelineStatusEvent};
+
+/// A finished recording waiting for its pipeline.
+pub struct QueuedRun {
+    pub run_id: String,
+    pub mode: PipelineMode,
+    /// Encoded by the pipeline, so the key listener that queued the run
+    /// doesn't wait for it.
+    pub recording: FinishedRecording,
+    /// Window focused when the recording started (see `focus`).
+    pub focused_app: Option<FocusedApp>,
+}
+
+/// Runs recordings through their pipeline one at a time, in the order they
+/// were made, so back-to-back dictations paste in order and don't fight over
+/// the tray and overlay. Recording doesn't wait for the queue: the next one
+/// can start while earlier ones are still being processed.
+pub struct RunQueue<T = QueuedRun> {
+    sender: mpsc::UnboundedSender<(String, T)>,
+    /// Runs waiting or in progress.
+    depth: Arc<AtomicUsize>,
+}
+
+impl RunQueue {
+    /// Create the queue and spawn the worker that drains it.
+    pub fn start(app: AppHandle) -> Self {
+        let worker_app = app.clone();
+        Self::spawn(
+            move |run: QueuedRun| {
+                let app = worker_app.clone();
+                async move {
+                    match run.mode {
+                        PipelineMode::Dictate => pipeline::run_pipeline(app, run).await,
+                        PipelineMode::Rewrite => pipeline::run_rewrite_pipeline(app, run).await,
+                    }
+                }
+            },
+            move |run_id, result, remaining| {
+                emit_depth(&app, remaining);
+                match result {
+                    Ok(()) => pipeline::reset_after_delay(&app, Duration::from_secs(2)),
+                    Err(e) => {
+                        eprintln!("Pipeline error: {}", e);
+                        let _ = app.emit("pipeline-status", PipelineStatusEvent::failed(run_id, &e));
+                        pipeline::reset_after_delay(&app, Duration::ZERO);
+                    }
+                }
+            },
+        )
+    }
+
+    pub fn push(&self, app: &AppHandle, run: QueuedRun) {
+        let _ = app.emit(
+            "pipeline-status",
+            PipelineStatusEvent::new(&run.run_id, PipelineStatus::Queued),
+        );
+        if let Some(depth) = self.enqueue(run.run_id.clone(), run) {
+            emit_depth(app, depth);
+        }
+    }
+}
+
+impl<T: Send + 'static> RunQueue<T> {
+    /// Spawn the worker: runs go to `process` one at a time, in order, and
+    /// each outcome to `finished` with the number of runs still queued. A run
+    /// that panics is reported as failed instead of stopping the worker.
+    fn spawn<F, Fut>(
+        mut process: F,
+        mut finished: impl FnMut(&str, Result<(), AppError>, usize) + Send + 'static,
+    ) -> Self
+    where
+        F: FnMut(T) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), AppError>> + Send + 'static,
+    {
+        let (sender, mut receiver) = mpsc::unbounded_channel::<(String, T)>();
+        let depth = Arc::new(AtomicUsize::new(0));
+        let worker_depth = Arc::clone(&depth);
+
+        tauri::async_runtime::spawn(async move {
+            while let Some((run_id, run)) = receiver.recv().await {
+                let result = AssertUnwindSafe(process(run))
+                    .catch_unwind()
+                    .await
+                    .unwrap_or_else(|panic| {
+                        let message = panic
+                            .downcast_ref::<&str>()
+                            .map(|s| s.to_string())
+                            .or_else(|| panic.downcast_ref::<String>().cloned())
+                            .unwrap_or_default();
+                        Err(AppError::Other(format!("Run crashed: {}", message)))
+                    });
+                let remaining = worker_depth.fetch_sub(1, Ordering::SeqCst) - 1;
+                finished(&run_id, result, remaining);
+            }
+        });
+
+        Self { sender, depth }
+    }
+
+    /// Queue `run`; returns the new depth, or `None` if the worker is gone.
+    fn enqueue(&self, run_id: String, run: T) -> Option<usize> {
+        let depth = self.depth.fetch_add(1, Ordering::SeqCst) + 1;
+        if let Err(e) = self.sender.send((run_id, run)) {
+            eprintln!("Run queue worker is gone, dropping run {}", (e.0).0);
+            self.depth.fetch_sub(1, Ordering::SeqCst);
+            return None;
+        }
+        Some(depth)
+    }
+
+    /// Runs waiting or in progress.
+    pub fn depth(&self) -> usize {
+        self.depth.load(Ordering::SeqCst)
+    }
+}
+
+fn emit_depth(app: &AppHandle, depth: usize) {
+    let _ = app.emit("pipeline-queue", depth);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Run id, result and remaining depth of a finished run.
+    type Outcome = (String, Result<(), String>, usize);
+
+    /// A queue whose runs sleep for the given milliseconds, then succeed,
+    /// fail (`u64::MAX`) or panic (`0`); outcomes arrive on the receiver.
+    fn queue() -> (
+        RunQueue<u64>,
+        Arc<Mutex<Vec<u64>>>,
+        mpsc::UnboundedReceiver<Outcome>,
+    ) {
+        let started = Arc::new(Mutex::new(Vec::new()));
+        let (outcomes, receiver) = mpsc::unbounded_channel();
+        let log = Arc::clone(&started);
+        let queue = RunQueue::spawn(
+            move |ms: u64| {
+                log.lock().unwrap().push(ms);
+                async move {
+                    match ms {
+                        0 => panic!("stage blew up"),
+                        u64::MAX => Err(AppError::Other("failed".to_string())),
+                        ms => {
+                            tokio::time::sleep(Duration::from_millis(ms)).await;
+                            Ok(())
+                        }
+                    }
+                }
+            },
+            move |run_id, result, remaining| {
+                let result = result.map_err(|e| e.to_string());
+                let _ = outcomes.send((run_id.to_string(), result, remaining));
+            },
+        );
+        (queue, started, receiver)
+    }
+
+    #[tokio::test]
+    async fn runs_one_at_a_time_in_order_and_count_down() {
+        let (queue, started, mut outcomes) = queue();
+        assert_eq!(queue.enqueue("a".to_string(), 50), Some(1));
+        assert_eq!(queue.enqueue("b".to_string(), 10), Some(2));
+        assert_eq!(queue.enqueue("c".to_string(), 1), Some(3));
+
+        for (id, remaining) in [("a", 2), ("b", 1), ("c", 0)] {
+            let (run_id, result, left) = outcomes.recv().await.unwrap();
+            assert_eq!((run_id.as_str(), left), (id, remaining));
+            assert!(result.is_ok());
+            assert_eq!(queue.depth(), remaining);
+        }
+        assert_eq!(*started.lock().unwrap(), vec![50, 10, 1]);
+    }
+
+    #[tokio::test]
+    async fn failed_and_panicking_runs_leave_the_queue_working() {
+        let (queue, _, mut outcomes) = queue();
+        queue.enqueue("failed".to_string(), u64::MAX);
+        queue.enqueue("panicked".to_string(), 0);
+        queue.enqueue("fine".to_string(), 1);
+
+        let (_, result, remaining) = outcomes.recv().await.unwrap();
+        assert_eq!((result, remaining), (Err("failed".to_string()), 2));
+        let (_, result, remaining) = outcomes.recv().await.unwrap();
+        assert_eq!((result, remaining), (Err("Run crashed: stage blew up".to_string()), 1));
+        let (run_id, result, remaining) = outcomes.recv().await.unwrap();
+        assert_eq!((run_id.as_str(), result, remaining), ("fine", Ok(()), 0));
+        assert_eq!(queue.depth(), 0);
+    }
+}
@@ -0,0 +1,119 @@
+use crate::error::AppError;
+
+const SERVICE: &str = "speech-ai-tool";
+
+/// Prefix marking a settings value as a reference to a keyring entry rather
+/// than the secret itself, e.g. `keyring:whisper_api_key`.
+const REFERENCE_PREFIX: &str = "keyring:";
+
+/// Keyring entry names for the secrets held in `AppSettings`.
+pub const WHISPER_API_KEY: &str = "whisper_api_key";
+pub const LLM_API_KEY: &str = "llm_api_key";
+
+pub fn reference(name: &str) -> String {
+    format!("{REFERENCE_PREFIX}{name}")
+}
+
+/// The keyring entry name `value` refers to, if it is a reference.
+pub fn referenced_name(value: &str) -> Option<&str> {
+    value.strip_prefix(REFERENCE_PREFIX)
+}
+
+fn entry(name: &str) -> Result<keyring::Entry, AppError> {
+    keyring::Entry::new(SERVICE, name).map_err(|e| AppError::Settings(format!("keyring: {e}")))
+}
+
+/// Read a secret. A missing entry is an empty secret, not an error.
+pub fn get(name: &str) -> Result<String, AppError> {
+    match entry(name)?.get_password() {
+        Ok(secret) => Ok(secret),
+        Err(keyring::Error::NoEntry) => Ok(String::new()),
+        Err(e) => Err(AppError::Settings(format!("keyring: {e}"))),
+    }
+}
+
+/// Store a secret; an empty one deletes the entry.
+pub fn set(name: &str, secret: &str) -> Result<(), AppError> {
+    let entry = entry(name)?;
+    let result = if secret.is_empty() {
+        match entry.delete_credential() {
+            Err(keyring::Error::NoEntry) => Ok(()),
+            other => other,
+        }
+    } else {
+        entry.set_password(secret)
+    };
+    result.map_err(|e| AppError::Settings(format!("keyring: {e}")))
+}
+
+/// Replace a secret with a reference to its keyring entry, for persisting.
+/// `previous` is the value persisted so far: an unchanged value (a reference
+/// as loaded, or a key kept in plain text) is returned as is without touching
+/// the keyring. If the platform has no usable secret store the value is kept
+/// as-is so the key still works, just unprotected.
+pub fn to_stored(name: &str, secret: &str, previous: &str) -> String {
+    if referenced_name(secret).is_some() || secret == previous {
+        return secret.to_string();
+    }
+    if secret.is_empty() {
+        if let Err(e) = set(name, "") {
+            eprintln!("Could not remove {} from the keyring: {}", name, e);
+        }
+        return String::new();
+    }
+    match set(name, secret) {
+        Ok(()) => reference(name),
+        Err(e) => {
+            eprintln!("Could not store {} in the keyring, keeping it in settings: {}", name, e);
+            secret.to_string()
+        }
+    }
+}
+
+/// Resolve a persisted value back to the secret. Plain-text values (from
+/// before keyring storage, or a fallback) pass through unchanged. If the
+/// keyring can't be read (locked, D-Bus timeout, ...) the reference is kept,
+/// so saving the settings again leaves the entry alone instead of deleting
+/// it as an emptied secret.
+pub fn from_stored(value: &str) -> String {
+    let Some(name) = referenced_name(value) else {
+        return value.to_string();
+    };
+    get(name).unwrap_or_else(|e| {
+        eprintln!("Could not read {} from the keyring: {}", name, e);
+        value.to_string()
+    })
+}
+
+/// The secret to send for a settings value. A reference left in place by a
+/// failed read is retried; while the keyring stays unavailable nothing is
+/// sent.
+pub fn usable(value: &str) -> String {
+    let secret = from_stored(value);
+    if referenced_name(&secret).is_some() {
+        String::new()
+    } else {
+        secret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn references_round_trip() {
+        assert_eq!(reference(LLM_API_KEY), "keyring:llm_api_key");
+        assert_eq!(referenced_name(&reference(LLM_API_KEY)), Some(LLM_API_KEY));
+        assert_eq!(referenced_name("sk-plain"), None);
+    }
+
+    #[test]
+    fn plaintext_values_pass_through() {
+        assert_eq!(from_stored("sk-plain"), "sk-plain");
+        assert_eq!(from_stored(""), "");
+        assert_eq!(usable("sk-plain"), "sk-plain");
+        assert_eq!(to_stored(LLM_API_KEY, "keyring:llm_api_key", ""), "keyring:llm_api_key");
+        assert_eq!(to_stored(LLM_API_KEY, "sk-kept", "sk-kept"), "sk-kept");
+    }
+}
@@ -1,34 +1,103 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 use crate::llm::LlmConfig;
+use crate::stages::PipelineProfile;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppSettings {
     pub audio_device_index: Option<usize>,
+    /// Stop a hotkey recording after this many seconds (0 = no limit), with
+    /// a warning tone shortly before, so a stuck key can't record for hours.
+    #[serde(default = "default_max_recording_secs")]
+    pub max_recording_secs: u64,
     pub hotkey: String,
+    /// Hold to speak an instruction that rewrites the selected text (e.g.
+    /// "make this more formal"). Empty = rewrite mode off.
+    #[serde(default)]
+    pub rewrite_hotkey: String,
     pub whisper_mode: WhisperMode,
     pub whisper_model: String,
     #[serde(default = "default_whisper_language")]
     pub whisper_language: String,
+    /// Unload the local model after this many minutes without use to give
+    /// the memory back; it is reloaded on the next hotkey press. 0 = never.
+    #[serde(default = "default_whisper_idle_unload_mins")]
+    pub whisper_idle_unload_mins: u64,
     pub whisper_api_endpoint: String,
+    /// A keyring reference once saved (see `secrets`); resolved only when a
+    /// request is built, so the key itself never reaches the webview.
     pub whisper_api_key: String,
     #[serde(default = "default_whisper_api_model")]
     pub whisper_api_model: String,
+    #[serde(default)]
+    pub whisper_api_protocol: WhisperApiProtocol,
+    /// Request path appended to the endpoint, overriding the protocol's
+    /// default. Empty = use the default.
+    #[serde(default)]
+    pub whisper_api_path: String,
+    /// Extra HTTP headers sent with every transcription request (e.g. a
+    /// reverse proxy's auth header).
+    #[serde(default)]
+    pub whisper_api_headers: BTreeMap<String, String>,
+    /// Request `verbose_json` and rebuild the text from segments, dropping
+    /// those the server marks as likely silence.
+    #[serde(default)]
+    pub whisper_api_verbose_json: bool,
+    /// Give up on a remote transcription after this many seconds (0 = no
+    /// limit) and move on to the fallback backend.
+    #[serde(default = "default_whisper_api_timeout_secs")]
+    pub whisper_api_timeout_secs: u64,
+    /// If the selected backend fails, retry with the other one (the local
+    /// model for API mode, the API for local mode when an endpoint is set).
+    #[serde(default = "default_whisper_fallback")]
+    pub whisper_fallback: bool,
     pub llm: LlmConfig,
     pub auto_paste: bool,
+    /// With auto-paste and LLM streaming on, type the cleaned text into the
+    /// target app as it is generated instead of pasting it at the end. Typed
+    /// text can't be taken back, so this only applies with the faithfulness
+    /// check off: the check needs the whole output before anything is used.
+    #[serde(default)]
+    pub progressive_paste: bool,
     #[serde(default = "default_paste_shortcut")]
     pub paste_shortcut: String,
+    /// Shortcut sent to copy the selection in rewrite mode.
+    #[serde(default = "default_copy_shortcut")]
+    pub copy_shortcut: String,
     pub history_max_items: usize,
+    /// Named stage lists for dictation (cleanup, replacements, output, ...).
+    #[serde(default = "crate::stages::default_profiles")]
+    pub pipeline_profiles: Vec<PipelineProfile>,
+    /// Name of the profile `run_pipeline` uses.
+    #[serde(default = "crate::stages::default_active_profile")]
+    pub active_profile: String,
+}
+
+pub fn default_max_recording_secs() -> u64 {
+    600
 }
 
 pub fn default_whisper_language() -> String {
     "en".to_string()
 }
 
+pub fn default_whisper_idle_unload_mins() -> u64 {
+    10
+}
+
 pub fn default_whisper_api_model() -> String {
     "whisper-1".to_string()
 }
 
+pub fn default_whisper_api_timeout_secs() -> u64 {
+    30
+}
+
+pub fn default_whisper_fallback() -> bool {
+    true
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum WhisperMode {
@@ -36,6 +105,18 @@ pub enum WhisperMode {
     Api,
 }
 
+/// Wire protocol of a remote transcription server.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WhisperApiProtocol {
+    /// `POST /v1/audio/transcriptions` — OpenAI, Groq, faster-whisper-server,
+    /// speaches, LocalAI, ...
+    #[default]
+    OpenAI,
+    /// whisper.cpp's `whisper-server`: `POST /inference`, no model field.
+    WhisperCpp,
+}
+
 pub fn default_paste_shortcut() -> String {
     if cfg!(target_os = "macos") {
         "Cmd+V".to_string()
@@ -46,29 +127,63 @@ pub fn default_paste_shortcut() -> String {
     }
 }
 
+pub fn default_copy_shortcut() -> String {
+    if cfg!(target_os = "macos") {
+        "Cmd+C".to_string()
+    } else {
+        "Ctrl+C".to_string()
+    }
+}
+
 impl Default for AppSettings {
     fn default() -> Self {
         Self {
             audio_device_index: None,
+            max_recording_secs: default_max_recording_secs(),
             hotkey: if cfg!(target_os = "macos") {
                 "MetaLeft+ShiftLeft+Space".to_string()
             } else {
                 "ControlLeft+ShiftLeft+Space".to_string()
             },
+            rewrite_hotkey: String::new(),
             whisper_mode: WhisperMode::Local,
             whisper_model: "large-v3-turbo-q5_0".to_string(),
             whisper_language: default_whisper_language(),
+            whisper_idle_unload_mins: default_whisper_idle_unload_mins(),
             whisper_api_endpoint: String::new(),
             whisper_api_key: String::new(),
             whisper_api_model: default_whisper_api_model(),
+            whisper_api_protocol: WhisperApiProtocol::default(),
+            whisper_api_path: String::new(),
+            whisper_api_headers: BTreeMap::new(),
+            whisper_api_verbose_json: false,
+            whisper_api_timeout_secs: default_whisper_api_timeout_secs(),
+            whisper_fallback: default_whisper_fallback(),
             llm: LlmConfig::default(),
             auto_paste: true,
+            progressive_paste: false,
             paste_shortcut: default_paste_shortcut(),
+            copy_shortcut: default_copy_shortcut(),
             history_max_items: 100,
+            pipeline_profiles: crate::stages::default_profiles(),
+            active_profile: crate::stages::default_active_profile(),
         }
     }
 }
 
+impl AppSettings {
+    /// `max_recording_secs` as a duration, `None` if unlimited.
+    pub fn max_recording_duration(&self) -> Option<std::time::Duration> {
+        (self.max_recording_secs > 0).then(|| std::time::Duration::from_secs(self.max_recording_secs))
+    }
+
+    /// Whether a recording should reload an idle-unloaded local model: only
+    /// when the local model is tried first and one is selected.
+    pub fn preloads_local_model(&self) -> bool {
+        self.whisper_mode == WhisperMode::Local && !self.whisper_model.is_empty()
+    }
+}
+
 pub fn load_settings(store: &tauri_plugin_store::Store<tauri::Wry>) -> AppSettings {
     let mut settings: AppSettings = store
         .get("settings")
@@ -98,18 +213,146 @@ pub fn load_settings(store: &tauri_plugin_store::Store<tauri::Wry>) -> AppSettin
         settings.llm.few_shot_examples = examples;
     }
 
+    // API keys live in the OS keyring and the store only holds references to
+    // them. Keys saved in plain text by older versions are moved over here.
+    if migrate_keys(&mut settings) {
+        if let Err(e) = save_settings(store, &settings) {
+            eprintln!("Failed to migrate API keys to the keyring: {}", e);
+        }
+    }
+
     settings
 }
 
+/// Move API keys held in plain text into the keyring, leaving references in
+/// their place. Returns whether any key moved.
+fn migrate_keys(settings: &mut AppSettings) -> bool {
+    let before = (settings.whisper_api_key.clone(), settings.llm.api_key.clone());
+    store_keys(settings, &AppSettings::default());
+    before != (settings.whisper_api_key.clone(), settings.llm.api_key.clone())
+}
+
+/// Replace the API keys in `settings` with keyring references, writing to the
+/// keyring only the keys that differ from `previous`. Every settings change is
+/// saved, and most leave the keys alone.
+fn store_keys(settings: &mut AppSettings, previous: &AppSettings) {
+    settings.whisper_api_key = crate::secrets::to_stored(
+        crate::secrets::WHISPER_API_KEY,
+        &settings.whisper_api_key,
+        &previous.whisper_api_key,
+    );
+    settings.llm.api_key =
+        crate::secrets::to_stored(crate::secrets::LLM_API_KEY, &settings.llm.api_key, &previous.llm.api_key);
+}
+
+/// Persist `settings` and return them as stored, with keyring references in
+/// place of any newly entered API keys; that is what to keep in memory.
 pub fn save_settings(
     store: &tauri_plugin_store::Store<tauri::Wry>,
     settings: &AppSettings,
-) -> Result<(), crate::error::AppError> {
-    let value = serde_json::to_value(settings)
+) -> Result<AppSettings, crate::error::AppError> {
+    let previous: AppSettings = store
+        .get("settings")
+        .and_then(|v| serde_json::from_value(v).ok())
+        .unwrap_or_default();
+    let mut stored = settings.clone();
+    store_keys(&mut stored, &previous);
+    let value = serde_json::to_value(&stored)
         .map_err(|e| crate::error::AppError::Settings(e.to_string()))?;
     store.set("settings", value);
     store
         .save()
         .map_err(|e| crate::error::AppError::Settings(e.to_string()))?;
-    Ok(())
+    Ok(stored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keyring::credential::{Credential, CredentialApi, CredentialBuilderApi};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    /// In-memory keyring shared by all entries, counting writes.
+    #[derive(Default)]
+    struct MockKeyring {
+        secrets: Mutex<HashMap<String, Vec<u8>>>,
+        writes: Mutex<usize>,
+    }
+
+    struct MockEntry(Arc<MockKeyring>, String);
+
+    impl CredentialApi for MockEntry {
+        fn set_secret(&self, secret: &[u8]) -> keyring::Result<()> {
+            *self.0.writes.lock().unwrap() += 1;
+            self.0.secrets.lock().unwrap().insert(self.1.clone(), secret.to_vec());
+            Ok(())
+        }
+
+        fn get_secret(&self) -> keyring::Result<Vec<u8>> {
+            self.0.secrets.lock().unwrap().get(&self.1).cloned().ok_or(keyring::Error::NoEntry)
+        }
+
+        fn delete_credential(&self) -> keyring::Result<()> {
+            *self.0.writes.lock().unwrap() += 1;
+            self.0.secrets.lock().unwrap().remove(&self.1).map(|_| ()).ok_or(keyring::Error::NoEntry)
+        }
+
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+    }
+
+    struct MockBuilder(Arc<MockKeyring>);
+
+    impl CredentialBuilderApi for MockBuilder {
+        fn build(&self, _target: Option<&str>, _service: &str, user: &str) -> keyring::Result<Box<Credential>> {
+            Ok(Box::new(MockEntry(Arc::clone(&self.0), user.to_string())))
+        }
+
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+    }
+
+    #[test]
+    fn only_a_selected_local_model_is_preloaded() {
+        let mut settings = AppSettings {
+            whisper_model: "small-q5_1".to_string(),
+            ..AppSettings::default()
+        };
+        assert!(settings.preloads_local_model());
+        settings.whisper_mode = WhisperMode::Api;
+        assert!(!settings.preloads_local_model());
+        settings.whisper_mode = WhisperMode::Local;
+        settings.whisper_model.clear();
+        assert!(!settings.preloads_local_model());
+    }
+
+    #[test]
+    fn plaintext_keys_move_to_the_keyring_and_unchanged_keys_are_not_rewritten() {
+        let keyring = Arc::new(MockKeyring::default());
+        keyring::set_default_credential_builder(Box::new(MockBuilder(Arc::clone(&keyring))));
+
+        let mut settings = AppSettings::default();
+        settings.llm.api_key = "sk-old".to_string();
+        assert!(migrate_keys(&mut settings));
+        assert_eq!(settings.llm.api_key, "keyring:llm_api_key");
+        assert_eq!(settings.whisper_api_key, "");
+        assert_eq!(crate::secrets::usable(&settings.llm.api_key), "sk-old");
+        assert_eq!(*keyring.writes.lock().unwrap(), 1);
+        assert!(!migrate_keys(&mut settings));
+
+        // Saving again with the references loaded from the store.
+        let previous = settings.clone();
+        store_keys(&mut settings, &previous);
+        assert_eq!(*keyring.writes.lock().unwrap(), 1);
+
+        // A newly entered key is written once and replaced by its reference.
+        settings.llm.api_key = "sk-new".to_string();
+        store_keys(&mut settings, &previous);
+        assert_eq!(settings.llm.api_key, "keyring:llm_api_key");
+        assert_eq!(crate::secrets::usable(&settings.llm.api_key), "sk-new");
+        assert_eq!(*keyring.writes.lock().unwrap(), 2);
+    }
 }
@@ -4,6 +4,8 @@ use std::thread;
 
 pub const START_TONE: &[u8] = include_bytes!("../sounds/start.wav");
 pub const STOP_TONE: &[u8] = include_bytes!("../sounds/stop.wav");
+/// Played shortly before a recording reaches the maximum length.
+pub const WARNING_TONE: &[u8] = include_bytes!("../sounds/warning.wav");
 
 pub struct SoundPlayer {
     tx: mpsc::Sender<Vec<u8>>,
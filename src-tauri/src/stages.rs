@@ -0,0 +1,674 @@
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+use crate::error::AppError;
+use crate::focus::FocusedApp;
+
+/// A dictation on its way through the pipeline's stages.
+#[derive(Debug, Clone, Default)]
+pub struct StageContext {
+    /// Identifies the run in status events and history.
+    pub run_id: String,
+    /// Name of the profile whose stages are running.
+    pub profile: String,
+    /// The transcription, as recognized.
+    pub raw_text: String,
+    /// The text as transformed by the stages so far.
+    pub text: String,
+    /// Transcription backend, recorded in history as `model_used`.
+    pub backend: String,
+    /// Length of the recorded audio.
+    pub duration_secs: f64,
+    pub focused_app: Option<FocusedApp>,
+    /// LLM used for cleanup, if it was attempted.
+    pub llm_model: String,
+    /// Milliseconds per step so far; the runner adds each stage's by name.
+    pub timings: BTreeMap<String, u64>,
+    /// Set by the runner for the current stage: whether it may type text
+    /// into the target app as it is generated. Only true when nothing but
+    /// sinks follow, one of them the output, so typed text can't go stale.
+    pub may_type: bool,
+    /// Text a transform already typed into the target app, so the output
+    /// sink pastes only what follows it (see `untyped_rest`).
+    pub typed: String,
+}
+
+impl StageContext {
+    /// The part of `text` that still has to be pasted after what was typed,
+    /// or `None` if the typed text isn't a prefix of the final text (the LLM
+    /// failed midway, or its answer was replaced). Pasting anything then
+    /// would garble what is already in the target app.
+    pub fn untyped_rest(&self) -> Option<&str> {
+        self.text.strip_prefix(self.typed.as_str())
+    }
+}
+
+/// Types streamed text into the target app piece by piece. The first failure
+/// stops it, so what it typed is always a prefix of what was generated.
+pub struct ProgressiveTyper<F> {
+    type_text: F,
+    typed: String,
+    failed: bool,
+}
+
+impl<F: FnMut(&str) -> Result<(), AppError>> ProgressiveTyper<F> {
+    pub fn new(type_text: F) -> Self {
+        Self {
+            type_text,
+            typed: String::new(),
+            failed: false,
+        }
+    }
+
+    pub fn push(&mut self, piece: &str) {
+        if self.failed {
+            return;
+        }
+        match (self.type_text)(piece) {
+            Ok(()) => self.typed.push_str(piece),
+            Err(e) => {
+                eprintln!("Progressive paste failed: {}", e);
+                self.failed = true;
+            }
+        }
+    }
+
+    /// What was typed successfully.
+    pub fn into_typed(self) -> String {
+        self.typed
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StageOutcome {
+    Continue,
+    /// A filter dropped the dictation; later stages don't run.
+    Stop(String),
+}
+
+/// What a stage does with the text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StageKind {
+    /// Changes `text`.
+    Transform,
+    /// May stop the pipeline.
+    Filter,
+    /// Delivers the text somewhere (paste, history, ...).
+    Sink,
+}
+
+/// One step of the dictation pipeline. Object-safe (boxed futures rather than
+/// `async fn`) so profiles can assemble stages from settings at runtime.
+pub trait Stage: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn kind(&self) -> StageKind;
+    fn run<'a>(&'a self, ctx: &'a mut StageContext) -> BoxFuture<'a, Result<StageOutcome, AppError>>;
+}
+
+/// Run `stages` in order, stopping at the first filter that drops the text or
+/// the first error.
+pub async fn run_stages(stages: &[Box<dyn Stage>], ctx: &mut StageContext) -> Result<StageOutcome, AppError> {
+    for (i, stage) in stages.iter().enumerate() {
+        let rest = &stages[i + 1..];
+        ctx.may_type = rest.iter().all(|s| s.kind() == StageKind::Sink)
+            && rest.iter().any(|s| s.name() == OUTPUT_STAGE);
+        let started = Instant::now();
+        let outcome = stage.run(ctx).await?;
+        *ctx.timings.entry(stage.name().to_string()).or_default() += started.elapsed().as_millis() as u64;
+        if let StageOutcome::Stop(reason) = outcome {
+            eprintln!("Pipeline stopped by {} stage: {}", stage.name(), reason);
+            return Ok(StageOutcome::Stop(reason));
+        }
+    }
+    Ok(StageOutcome::Continue)
+}
+
+/// `Stage::name` of the sink that pastes into the target app.
+pub const OUTPUT_STAGE: &str = "output";
+
+/// A stage as configured in a profile.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StageConfig {
+    /// LLM cleanup with the `llm` settings.
+    Cleanup,
+    /// Find/replace rules, e.g. for names the recognizer keeps getting wrong.
+    Replace { rules: Vec<ReplaceRule> },
+    /// Drop dictations that are too short or are known noise (e.g. the
+    /// "Thank you." whisper hallucinates on silence).
+    Filter {
+        #[serde(default)]
+        min_words: usize,
+        #[serde(default)]
+        ignore: Vec<String>,
+    },
+    /// Pipe the text and metadata as JSON to an external command and use
+    /// what it prints instead (see `ScriptStage`).
+    Script {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+        #[serde(default = "default_script_timeout_secs")]
+        timeout_secs: u64,
+    },
+    /// Copy to the clipboard and, with auto-paste, paste into the target app.
+    Output,
+    /// Save to history.
+    History,
+}
+
+/// A named, ordered list of stages; `active_profile` picks the one to run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PipelineProfile {
+    pub name: String,
+    pub stages: Vec<StageConfig>,
+}
+
+pub fn default_script_timeout_secs() -> u64 {
+    10
+}
+
+pub const DEFAULT_PROFILE: &str = "Default";
+
+impl Default for PipelineProfile {
+    /// The fixed pipeline of earlier versions.
+    fn default() -> Self {
+        Self {
+            name: DEFAULT_PROFILE.to_string(),
+            stages: vec![StageConfig::Cleanup, StageConfig::Output, StageConfig::History],
+        }
+    }
+}
+
+pub fn default_profiles() -> Vec<PipelineProfile> {
+    vec![PipelineProfile::default()]
+}
+
+pub fn default_active_profile() -> String {
+    DEFAULT_PROFILE.to_string()
+}
+
+/// The profile named `active`, falling back to the default pipeline.
+pub fn active_profile(profiles: &[PipelineProfile], active: &str) -> PipelineProfile {
+    profiles
+        .iter()
+        .find(|p| p.name == active)
+        .cloned()
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplaceRule {
+    pub find: String,
+    pub replace: String,
+    /// Match case exactly; by default ASCII case is ignored.
+    #[serde(default)]
+    pub case_sensitive: bool,
+}
+
+/// Applies `ReplaceRule`s in order, to whole words only.
+pub struct ReplaceStage {
+    pub rules: Vec<ReplaceRule>,
+}
+
+impl Stage for ReplaceStage {
+    fn name(&self) -> &'static str {
+        "replace"
+    }
+
+    fn kind(&self) -> StageKind {
+        StageKind::Transform
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut StageContext) -> BoxFuture<'a, Result<StageOutcome, AppError>> {
+        Box::pin(async move {
+            for rule in &self.rules {
+                ctx.text = replace_words(&ctx.text, rule);
+            }
+            Ok(StageOutcome::Continue)
+        })
+    }
+}
+
+fn replace_words(text: &str, rule: &ReplaceRule) -> String {
+    let find = rule.find.as_str();
+    if find.is_empty() {
+        return text.to_string();
+    }
+    let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '\'');
+
+    let mut out = String::with_capacity(text.len());
+    let mut rest = 0;
+    let mut i = 0;
+    while i + find.len() <= text.len() {
+        if !text.is_char_boundary(i) || !text.is_char_boundary(i + find.len()) {
+            i += 1;
+            continue;
+        }
+        let candidate = &text[i..i + find.len()];
+        let matches = if rule.case_sensitive {
+            candidate == find
+        } else {
+            candidate.eq_ignore_ascii_case(find)
+        };
+        let bounded = !is_word(text[..i].chars().next_back())
+            && !is_word(text[i + find.len()..].chars().next());
+        if matches && bounded {
+            out.push_str(&text[rest..i]);
+            out.push_str(&rule.replace);
+            i += find.len();
+            rest = i;
+        } else {
+            i += 1;
+        }
+    }
+    out.push_str(&text[rest..]);
+    out
+}
+
+/// Stops the pipeline for dictations shorter than `min_words` or equal to one
+/// of the `ignore` phrases (ignoring case and surrounding punctuation).
+pub struct FilterStage {
+    pub min_words: usize,
+    pub ignore: Vec<String>,
+}
+
+impl Stage for FilterStage {
+    fn name(&self) -> &'static str {
+        "filter"
+    }
+
+    fn kind(&self) -> StageKind {
+        StageKind::Filter
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut StageContext) -> BoxFuture<'a, Result<StageOutcome, AppError>> {
+        Box::pin(async move {
+            let words = ctx.text.split_whitespace().count();
+            if words < self.min_words {
+                return Ok(StageOutcome::Stop(format!("only {} word(s)", words)));
+            }
+            let normalize = |s: &str| {
+                s.trim_matches(|c: char| !c.is_alphanumeric())
+                    .to_lowercase()
+            };
+            let text = normalize(&ctx.text);
+            if self.ignore.iter().any(|phrase| normalize(phrase) == text) {
+                return Ok(StageOutcome::Stop(format!("ignored phrase {:?}", ctx.text.trim())));
+            }
+            Ok(StageOutcome::Continue)
+        })
+    }
+}
+
+/// What a script stage sends on stdin.
+#[derive(Serialize)]
+struct ScriptInput<'a> {
+    text: &'a str,
+    raw_text: &'a str,
+    backend: &'a str,
+    duration_secs: f64,
+    app: Option<&'a FocusedApp>,
+}
+
+/// Runs an external command (an executable or a script with a shebang) with
+/// the dictation as JSON on stdin; what it prints on stdout, trimmed, becomes
+/// the text. Empty output drops the dictation. A failing, non-zero or
+/// timed-out command leaves the text as it was, like an unavailable LLM.
+pub struct ScriptStage {
+    pub command: String,
+    pub args: Vec<String>,
+    pub timeout: std::time::Duration,
+}
+
+impl ScriptStage {
+    async fn run_command(&self, input: &[u8]) -> Result<String, String> {
+        use tokio::io::AsyncWriteExt;
+
+        let mut child = tokio::process::Command::new(&self.command)
+            .args(&self.args)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("failed to start {}: {}", self.command, e))?;
+
+        // Write the input while waiting for the output, both within the
+        // timeout: a script that doesn't read its input would otherwise block
+        // the write once the pipe is full. One that closes the pipe early is
+        // not an error. On timeout the child is dropped, which kills it.
+        let stdin = child.stdin.take();
+        let write = async move {
+            if let Some(mut stdin) = stdin {
+                let _ = stdin.write_all(input).await;
+            }
+        };
+        let run = async { tokio::join!(write, child.wait_with_output()).1 };
+        let output = tokio::time::timeout(self.timeout, run)
+            .await
+            .map_err(|_| format!("no result within {}s", self.timeout.as_secs()))?
+            .map_err(|e| e.to_string())?;
+        if !output.status.success() {
+            return Err(format!(
+                "{} ({})",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        String::from_utf8(output.stdout).map_err(|_| "output is not UTF-8".to_string())
+    }
+}
+
+impl Stage for ScriptStage {
+    fn name(&self) -> &'static str {
+        "script"
+    }
+
+    fn kind(&self) -> StageKind {
+        StageKind::Transform
+    }
+
+    fn run<'a>(&'a self, ctx: &'a mut StageContext) -> BoxFuture<'a, Result<StageOutcome, AppError>> {
+        Box::pin(async move {
+            let input = serde_json::to_vec(&ScriptInput {
+                text: &ctx.text,
+                raw_text: &ctx.raw_text,
+                backend: &ctx.backend,
+                duration_secs: ctx.duration_secs,
+                app: ctx.focused_app.as_ref(),
+            })
+            .map_err(|e| AppError::Other(e.to_string()))?;
+
+            match self.run_command(&input).await {
+                Ok(output) if output.trim().is_empty() => {
+                    return Ok(StageOutcome::Stop(format!("{} printed nothing", self.command)));
+                }
+                Ok(output) => ctx.text = output.trim().to_string(),
+                Err(e) => eprintln!("Script stage failed, keeping the text: {}", e),
+            }
+            Ok(StageOutcome::Continue)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx(text: &str) -> StageContext {
+        StageContext {
+            raw_text: text.to_string(),
+            text: text.to_string(),
+            ..StageContext::default()
+        }
+    }
+
+    fn rule(find: &str, replace: &str) -> ReplaceRule {
+        ReplaceRule {
+            find: find.to_string(),
+            replace: replace.to_string(),
+            case_sensitive: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn replace_matches_whole_words_only() {
+        let stage = ReplaceStage {
+            rules: vec![rule("jason", "JSON"), rule("new line", "\n")],
+        };
+        let mut ctx = ctx("Jason's parser reads Jason, not jasonette. New line done");
+        stage.run(&mut ctx).await.unwrap();
+        assert_eq!(ctx.text, "Jason's parser reads JSON, not jasonette. \n done");
+    }
+
+    #[tokio::test]
+    async fn filter_stops_short_and_ignored_dictations() {
+        let stage = FilterStage {
+            min_words: 2,
+            ignore: vec!["thank you".to_string()],
+        };
+        assert!(matches!(stage.run(&mut ctx("Okay.")).await.unwrap(), StageOutcome::Stop(_)));
+        assert!(matches!(stage.run(&mut ctx("Thank you.")).await.unwrap(), StageOutcome::Stop(_)));
+        assert_eq!(
+            stage.run(&mut ctx("Thank you, see you soon.")).await.unwrap(),
+            StageOutcome::Continue
+        );
+    }
+
+    #[tokio::test]
+    async fn runner_stops_at_the_first_filter() {
+        let stages: Vec<Box<dyn Stage>> = vec![
+            Box::new(ReplaceStage {
+                rules: vec![rule("um", "")],
+            }),
+            Box::new(FilterStage {
+                min_words: 1,
+                ignore: Vec::new(),
+            }),
+            Box::new(ReplaceStage {
+                rules: vec![rule("", "never")],
+            }),
+        ];
+        let mut ctx = ctx("um");
+        assert!(matches!(run_stages(&stages, &mut ctx).await.unwrap(), StageOutcome::Stop(_)));
+        assert_eq!(ctx.text, "");
+    }
+
+    type Log = std::sync::Arc<std::sync::Mutex<Vec<(&'static str, bool)>>>;
+
+    /// Logs its name and `may_type` when run.
+    struct Probe(&'static str, StageKind, Log);
+
+    impl Stage for Probe {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn kind(&self) -> StageKind {
+            self.1
+        }
+
+        fn run<'a>(&'a self, ctx: &'a mut StageContext) -> BoxFuture<'a, Result<StageOutcome, AppError>> {
+            self.2.lock().unwrap().push((self.0, ctx.may_type));
+            Box::pin(async { Ok(StageOutcome::Continue) })
+        }
+    }
+
+    #[tokio::test]
+    async fn only_a_stage_followed_by_sinks_including_output_may_type() {
+        let log = Log::default();
+        let probe = |name, kind| -> Box<dyn Stage> { Box::new(Probe(name, kind, log.clone())) };
+        let stages = vec![
+            probe("cleanup", StageKind::Transform),
+            probe("replace", StageKind::Transform),
+            probe("history", StageKind::Sink),
+            probe(OUTPUT_STAGE, StageKind::Sink),
+        ];
+        run_stages(&stages, &mut ctx("hi")).await.unwrap();
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![("cleanup", false), ("replace", true), ("history", true), (OUTPUT_STAGE, false)]
+        );
+    }
+
+    #[tokio::test]
+    async fn cleanup_may_not_type_when_a_transform_or_filter_follows() {
+        let log = Log::default();
+        let probe = |name, kind| -> Box<dyn Stage> { Box::new(Probe(name, kind, log.clone())) };
+        let followers: Vec<Box<dyn Stage>> = vec![
+            Box::new(ReplaceStage {
+                rules: vec![rule("hi", "hello")],
+            }),
+            Box::new(FilterStage {
+                min_words: 0,
+                ignore: Vec::new(),
+            }),
+            Box::new(ScriptStage {
+                command: "true".to_string(),
+                args: Vec::new(),
+                timeout: std::time::Duration::from_secs(5),
+            }),
+        ];
+        for follower in followers {
+            let stages = vec![probe("cleanup", StageKind::Transform), follower, probe(OUTPUT_STAGE, StageKind::Sink)];
+            run_stages(&stages, &mut ctx("hi")).await.unwrap();
+        }
+        let cleanup: Vec<bool> = log
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(name, _)| *name == "cleanup")
+            .map(|(_, may_type)| *may_type)
+            .collect();
+        assert_eq!(cleanup, vec![false, false, false]);
+    }
+
+    #[test]
+    fn interrupted_typing_is_completed_without_repeating_text() {
+        let pieces = ["Hello", " there,", " how", " are you?"];
+        let keystrokes = std::sync::Mutex::new(String::new());
+        let mut calls = 0;
+        // Fails from the third piece on, like a target app losing focus.
+        let mut typer = ProgressiveTyper::new(|piece: &str| {
+            calls += 1;
+            if calls > 2 {
+                return Err(AppError::Output("typing failed".into()));
+            }
+            keystrokes.lock().unwrap().push_str(piece);
+            Ok(())
+        });
+        for piece in pieces {
+            typer.push(piece);
+        }
+        let mut ctx = ctx("hello there how are you");
+        ctx.typed = typer.into_typed();
+        assert_eq!(ctx.typed, *keystrokes.lock().unwrap());
+
+        // The cleaned text starts with what was typed: paste only the rest.
+        ctx.text = pieces.concat();
+        let rest = ctx.untyped_rest().unwrap();
+        assert_eq!(rest, " how are you?");
+        assert_eq!(format!("{}{}", keystrokes.lock().unwrap(), rest), ctx.text);
+
+        // The LLM failed and the raw text is used: nothing may be pasted.
+        ctx.text = ctx.raw_text.clone();
+        assert_eq!(ctx.untyped_rest(), None);
+    }
+
+    /// Output sink that records the text it would paste.
+    struct Capture(std::sync::Arc<std::sync::Mutex<Vec<String>>>);
+
+    impl Stage for Capture {
+        fn name(&self) -> &'static str {
+            OUTPUT_STAGE
+        }
+
+        fn kind(&self) -> StageKind {
+            StageKind::Sink
+        }
+
+        fn run<'a>(&'a self, ctx: &'a mut StageContext) -> BoxFuture<'a, Result<StageOutcome, AppError>> {
+            self.0.lock().unwrap().push(ctx.text.clone());
+            Box::pin(async { Ok(StageOutcome::Continue) })
+        }
+    }
+
+    #[tokio::test]
+    async fn dictation_runs_offline_from_transcription_to_output() {
+        use crate::transcribe::{transcribe_with_fallback, Backend, MockTranscriber};
+
+        let backend = |name, result: Result<&str, &str>| Backend {
+            transcriber: Box::new(MockTranscriber {
+                name,
+                result: result.map(str::to_string).map_err(str::to_string),
+                delay: std::time::Duration::ZERO,
+            }),
+            timeout: None,
+        };
+        let pasted = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let stages: Vec<Box<dyn Stage>> = vec![
+            Box::new(ReplaceStage {
+                rules: vec![rule("jason", "JSON")],
+            }),
+            Box::new(FilterStage {
+                min_words: 2,
+                ignore: vec!["thank you".to_string()],
+            }),
+            Box::new(Capture(pasted.clone())),
+        ];
+
+        for (spoken, outcome) in [
+            ("  send the jason file \n", StageOutcome::Continue),
+            ("Thank you.", StageOutcome::Stop("ignored phrase \"Thank you.\"".to_string())),
+        ] {
+            let chain = [backend("local", Err("no model loaded")), backend("api", Ok(spoken))];
+            // The mocks ignore the audio, so any bytes will do.
+            let transcription = transcribe_with_fallback(&chain, b"RIFF", "en").await.unwrap().unwrap();
+            assert_eq!(transcription.backend, "api");
+
+            let mut ctx = StageContext {
+                raw_text: transcription.text.clone(),
+                text: transcription.text,
+                backend: transcription.backend,
+                ..StageContext::default()
+            };
+            assert_eq!(run_stages(&stages, &mut ctx).await.unwrap(), outcome);
+        }
+        assert_eq!(*pasted.lock().unwrap(), vec!["send the JSON file".to_string()]);
+    }
+
+    #[test]
+    fn unknown_profile_falls_back_to_the_default_pipeline() {
+        let profiles = vec![PipelineProfile {
+            name: "Notes".to_string(),
+            stages: vec![StageConfig::History],
+        }];
+        assert_eq!(active_profile(&profiles, "Notes").stages, vec![StageConfig::History]);
+        assert_eq!(active_profile(&profiles, "Gone"), PipelineProfile::default());
+    }
+
+    #[cfg(unix)]
+    fn script(body: &str, timeout_secs: u64) -> ScriptStage {
+        ScriptStage {
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), body.to_string()],
+            timeout: std::time::Duration::from_secs(timeout_secs),
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn script_receives_json_and_replaces_the_text() {
+        let stage = script(r#"tr a-z A-Z | sed 's/.*"TEXT":"\([^"]*\)".*/\1/'"#, 5);
+        let mut ctx = ctx("hello there");
+        assert_eq!(stage.run(&mut ctx).await.unwrap(), StageOutcome::Continue);
+        assert_eq!(ctx.text, "HELLO THERE");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn failing_or_slow_scripts_keep_the_text_and_empty_output_stops() {
+        for stage in [script("echo oops >&2; exit 3", 5), script("sleep 5", 1)] {
+            let mut ctx = ctx("keep me");
+            assert_eq!(stage.run(&mut ctx).await.unwrap(), StageOutcome::Continue);
+            assert_eq!(ctx.text, "keep me");
+        }
+
+        let mut ctx = ctx("drop me");
+        assert!(matches!(script("cat >/dev/null", 5).run(&mut ctx).await.unwrap(), StageOutcome::Stop(_)));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn scripts_that_never_read_their_input_time_out() {
+        // Far more than a pipe buffer holds, so writing it blocks.
+        let mut ctx = ctx(&"word ".repeat(200_000));
+        let started = std::time::Instant::now();
+        assert_eq!(script("sleep 30", 1).run(&mut ctx).await.unwrap(), StageOutcome::Continue);
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        assert!(ctx.text.starts_with("word word"));
+    }
+}
@@ -0,0 +1,140 @@
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+/// A canned HTTP response for [`serve`].
+pub struct StubResponse {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl StubResponse {
+    pub fn json(status: u16, body: &str) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: body.to_string(),
+        }
+    }
+}
+
+/// Start a local HTTP server that answers one connection per entry in
+/// `responses`, in order. Returns its base URL and a handle resolving to the
+/// raw requests it received (headers and body, lossily decoded).
+pub async fn serve(responses: Vec<StubResponse>) -> (String, JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    let handle = tokio::spawn(async move {
+        let mut requests = Vec::new();
+        for response in responses {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            requests.push(read_request(&mut socket).await);
+            write_response(&mut socket, &response).await;
+        }
+        requests
+    });
+
+    (url, handle)
+}
+
+/// Like [`serve`], but answers `count` connections with whatever `respond`
+/// returns for each raw request, for stubs whose replies depend on the
+/// request rather than on the order requests arrive in.
+pub async fn serve_with<F>(count: usize, respond: F) -> (String, JoinHandle<Vec<String>>)
+where
+    F: Fn(&str) -> StubResponse + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    let handle = tokio::spawn(async move {
+        let mut requests = Vec::new();
+        for _ in 0..count {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let request = read_request(&mut socket).await;
+            write_response(&mut socket, &respond(&request)).await;
+            requests.push(request);
+        }
+        requests
+    });
+
+    (url, handle)
+}
+
+/// Start a local HTTP server that answers one connection with a streamed
+/// body, writing one of `chunks` every `interval`.
+pub async fn serve_slowly(
+    content_type: &'static str,
+    chunks: Vec<String>,
+    interval: std::time::Duration,
+) -> (String, JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    let handle = tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let request = read_request(&mut socket).await;
+        let head = format!(
+            "HTTP/1.1 200 Stub\r\nContent-Type: {}\r\nConnection: close\r\n\r\n",
+            content_type
+        );
+        let _ = socket.write_all(head.as_bytes()).await;
+        for chunk in chunks {
+            tokio::time::sleep(interval).await;
+            let _ = socket.write_all(chunk.as_bytes()).await;
+            let _ = socket.flush().await;
+        }
+        let _ = socket.shutdown().await;
+        request
+    });
+
+    (url, handle)
+}
+
+async fn write_response(socket: &mut tokio::net::TcpStream, response: &StubResponse) {
+    let head = format!(
+        "HTTP/1.1 {} Stub\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len()
+    );
+    let _ = socket.write_all(head.as_bytes()).await;
+    let _ = socket.write_all(response.body.as_bytes()).await;
+    let _ = socket.shutdown().await;
+}
+
+async fn read_request(socket: &mut tokio::net::TcpStream) -> String {
+    let mut data = Vec::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = socket.read(&mut buf).await.unwrap_or(0);
+        if n == 0 {
+            break;
+        }
+        data.extend_from_slice(&buf[..n]);
+        if request_complete(&data) {
+            break;
+        }
+    }
+    String::from_utf8_lossy(&data).into_owned()
+}
+
+fn request_complete(data: &[u8]) -> bool {
+    let Some(header_end) = data.windows(4).position(|w| w == b"\r\n\r\n") else {
+        return false;
+    };
+    let headers = String::from_utf8_lossy(&data[..header_end]).to_lowercase();
+    let body = &data[header_end + 4..];
+
+    if headers.contains("transfer-encoding: chunked") {
+        return body.ends_with(b"0\r\n\r\n");
+    }
+    let content_length = headers
+        .lines()
+        .find_map(|l| l.strip_prefix("content-length:"))
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    body.len() >= content_length
+}
@@ -0,0 +1,513 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+
+use crate::error::AppError;
+use crate::settings::{AppSettings, WhisperApiProtocol, WhisperMode};
+use crate::whisper::{WhisperApiConfig, WhisperEngine};
+
+/// A speech-to-text backend. Object-safe (boxed futures rather than `async fn`)
+/// so the pipeline can pick an implementation from settings at runtime.
+pub trait Transcriber: Send + Sync {
+    /// Human-readable label, recorded in history as `model_used`.
+    fn name(&self) -> String;
+
+    fn transcribe<'a>(
+        &'a self,
+        wav_bytes: &'a [u8],
+        language: &'a str,
+    ) -> BoxFuture<'a, Result<String, AppError>>;
+}
+
+/// The in-process whisper.cpp engine. Inference is blocking, so it runs on the
+/// blocking thread pool.
+pub struct LocalWhisper {
+    engine: Arc<WhisperEngine>,
+    model: String,
+}
+
+impl LocalWhisper {
+    pub fn new(engine: Arc<WhisperEngine>, model: &str) -> Self {
+        Self {
+            engine,
+            model: model.to_string(),
+        }
+    }
+}
+
+impl Transcriber for LocalWhisper {
+    fn name(&self) -> String {
+        self.model.clone()
+    }
+
+    fn transcribe<'a>(
+        &'a self,
+        wav_bytes: &'a [u8],
+        language: &'a str,
+    ) -> BoxFuture<'a, Result<String, AppError>> {
+        let engine = Arc::clone(&self.engine);
+        let model = self.model.clone();
+        let wav_bytes = wav_bytes.to_vec();
+        let language = language.to_string();
+        Box::pin(async move {
+            tauri::async_runtime::spawn_blocking(move || {
+                engine.select_model(&model);
+                engine.transcribe(&wav_bytes, &language)
+            })
+            .await
+            .map_err(|e| AppError::Whisper(format!("task join error: {e}")))?
+        })
+    }
+}
+
+/// A remote transcription server (OpenAI-compatible or whisper.cpp).
+pub struct ApiWhisper {
+    config: WhisperApiConfig,
+}
+
+impl ApiWhisper {
+    pub fn new(config: WhisperApiConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl Transcriber for ApiWhisper {
+    fn name(&self) -> String {
+        match self.config.protocol {
+            WhisperApiProtocol::OpenAI => format!("{} (API)", self.config.model),
+            WhisperApiProtocol::WhisperCpp => "whisper.cpp server".to_string(),
+        }
+    }
+
+    fn transcribe<'a>(
+        &'a self,
+        wav_bytes: &'a [u8],
+        language: &'a str,
+    ) -> BoxFuture<'a, Result<String, AppError>> {
+        Box::pin(crate::whisper::transcribe_via_api(&self.config, wav_bytes, language))
+    }
+}
+
+/// One entry in the fallback chain. `timeout` bounds a single attempt (per
+/// chunk of a long recording); the local engine has none because abandoning
+/// it would not free the model lock.
+pub struct Backend {
+    pub transcriber: Box<dyn Transcriber>,
+    pub timeout: Option<Duration>,
+}
+
+/// Recordings longer than this are transcribed in pieces: uploads stay well
+/// under API size limits (16 kHz 16-bit mono is about 1.9 MB a minute) and
+/// the local engine doesn't hold hours of audio at once.
+const CHUNK: Duration = Duration::from_secs(120);
+
+/// How much consecutive pieces overlap, so a word cut at one boundary is
+/// whole in the other piece. The duplicate text is removed by `stitch`.
+const CHUNK_OVERLAP: Duration = Duration::from_secs(2);
+
+/// How many words at the end and start of neighbouring pieces `stitch`
+/// searches for the overlap.
+const STITCH_WINDOW: usize = 12;
+
+/// Text produced by the chain, tagged with the backend that produced it (or
+/// the backends, in order, when one took over partway through).
+pub struct Transcription {
+    pub text: String,
+    pub backend: String,
+}
+
+/// Build the ordered backend chain: the backend selected by `whisper_mode`
+/// first, then (with `whisper_fallback` on) the other one.
+pub fn chain_from_settings(settings: &AppSettings, engine: &Arc<WhisperEngine>) -> Vec<Backend> {
+    let local = || Backend {
+        transcriber: Box::new(LocalWhisper::new(Arc::clone(engine), &settings.whisper_model)),
+        timeout: None,
+    };
+    let api = || Backend {
+        transcriber: Box::new(ApiWhisper::new(WhisperApiConfig::from_settings(settings))),
+        timeout: (settings.whisper_api_timeout_secs > 0)
+            .then(|| Duration::from_secs(settings.whisper_api_timeout_secs)),
+    };
+    let api_configured = !settings.whisper_api_endpoint.trim().is_empty();
+
+    let mut chain = Vec::new();
+    match settings.whisper_mode {
+        WhisperMode::Local => {
+            chain.push(local());
+            if settings.whisper_fallback && api_configured {
+                chain.push(api());
+            }
+        }
+        WhisperMode::Api => {
+            chain.push(api());
+            if settings.whisper_fallback {
+                chain.push(local());
+            }
+        }
+    }
+    chain
+}
+
+/// Try each backend in order until one succeeds. A backend that errors or
+/// exceeds its timeout hands over to the next, which resumes at the chunk
+/// that failed; one that returns no speech is a valid answer and ends the
+/// chain with `None`.
+pub async fn transcribe_with_fallback(
+    chain: &[Backend],
+    wav_bytes: &[u8],
+    language: &str,
+) -> Result<Option<Transcription>, AppError> {
+    // An unreadable WAV is passed on whole for the backend to reject.
+    let chunks = crate::whisper::split_wav(wav_bytes, CHUNK, CHUNK_OVERLAP)
+        .unwrap_or_else(|_| vec![wav_bytes.to_vec()]);
+    transcribe_chunks_with_fallback(chain, &chunks, language).await
+}
+
+async fn transcribe_chunks_with_fallback(
+    chain: &[Backend],
+    chunks: &[Vec<u8>],
+    language: &str,
+) -> Result<Option<Transcription>, AppError> {
+    // One entry per chunk transcribed so far, `None` for silence.
+    let mut texts = Vec::new();
+    let mut used = Vec::new();
+    let mut failures = Vec::new();
+
+    for backend in chain {
+        let name = backend.transcriber.name();
+        let done = texts.len();
+        let result = transcribe_chunks(backend, &chunks[done..], language, &mut texts).await;
+        if texts.len() > done {
+            used.push(name.clone());
+        }
+        match result {
+            Ok(()) => {
+                let texts: Vec<String> = texts.into_iter().flatten().collect();
+                return Ok((!texts.is_empty()).then(|| Transcription {
+                    text: stitch(&texts),
+                    backend: used.join(", "),
+                }));
+            }
+            Err(e) => {
+                eprintln!("Transcription backend {} failed: {}", name, e);
+                failures.push(format!("{}: {}", name, e));
+            }
+        }
+    }
+
+    Err(AppError::Whisper(if failures.is_empty() {
+        "No transcription backend configured".to_string()
+    } else {
+        failures.join("; ")
+    }))
+}
+
+/// Transcribe `chunks` in order with one backend, appending each chunk's
+/// text to `texts` as it finishes, so the chunks before a failure are kept.
+/// The backend's timeout bounds each chunk.
+async fn transcribe_chunks(
+    backend: &Backend,
+    chunks: &[Vec<u8>],
+    language: &str,
+    texts: &mut Vec<Option<String>>,
+) -> Result<(), AppError> {
+    for chunk in chunks {
+        let attempt = transcribe_speech(backend.transcriber.as_ref(), chunk, language);
+        let text = match backend.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, attempt)
+                .await
+                .unwrap_or_else(|_| {
+                    Err(AppError::Whisper(format!(
+                        "timed out after {}s",
+                        timeout.as_secs()
+                    )))
+                })?,
+            None => attempt.await?,
+        };
+        texts.push(text);
+    }
+    Ok(())
+}
+
+/// Join the transcriptions of overlapping chunks. The overlap is usually in
+/// both, with the words at its edges cut off or misheard in one of them, so
+/// the longest run of words the end of one piece shares with the start of the
+/// next is kept once. Without such a run (two words or more) the pieces are
+/// simply joined. A run at the very start of a piece keeps the earlier
+/// piece's first word, so a chunk that begins mid-sentence doesn't bring its
+/// capital letter along.
+pub fn stitch(parts: &[String]) -> String {
+    if let [only] = parts {
+        return only.clone();
+    }
+    let normalize = |word: &str| -> String {
+        word.chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect()
+    };
+
+    let mut words: Vec<&str> = Vec::new();
+    for part in parts {
+        let next: Vec<&str> = part.split_whitespace().collect();
+        let tail_start = words.len().saturating_sub(STITCH_WINDOW);
+        let tail: Vec<String> = words[tail_start..].iter().map(|w| normalize(w)).collect();
+        let head: Vec<String> = next.iter().take(STITCH_WINDOW).map(|w| normalize(w)).collect();
+
+        // (length, start in tail, start in head) of the longest common run
+        let mut best = (0, 0, 0);
+        for i in 0..tail.len() {
+            for j in 0..head.len() {
+                let len = tail[i..]
+                    .iter()
+                    .zip(&head[j..])
+                    .take_while(|(a, b)| !a.is_empty() && a == b)
+                    .count();
+                if len > best.0 {
+                    best = (len, i, j);
+                }
+            }
+        }
+
+        if best.0 >= 2 {
+            // The next piece heard the overlap with context on both sides,
+            // except at its very first word, which it capitalizes as if a
+            // sentence started there.
+            let skip = usize::from(best.2 == 0);
+            words.truncate(tail_start + best.1 + skip);
+            words.extend(&next[best.2 + skip..]);
+        } else {
+            words.extend(next);
+        }
+    }
+    words.join(" ")
+}
+
+/// Run `transcriber` and normalize its output: surrounding whitespace is
+/// trimmed and a transcription with no speech comes back as `None`.
+async fn transcribe_speech(
+    transcriber: &dyn Transcriber,
+    wav_bytes: &[u8],
+    language: &str,
+) -> Result<Option<String>, AppError> {
+    let text = transcriber.transcribe(wav_bytes, language).await?;
+    let text = text.trim();
+    Ok(if text.is_empty() {
+        None
+    } else {
+        Some(text.to_string())
+    })
+}
+
+/// Canned backend for exercising the pipeline without a model or network.
+#[cfg(test)]
+pub struct MockTranscriber {
+    pub name: &'static str,
+    pub result: Result<String, String>,
+    pub delay: Duration,
+}
+
+#[cfg(test)]
+impl Transcriber for MockTranscriber {
+    fn name(&self) -> String {
+        self.name.to_string()
+    }
+
+    fn transcribe<'a>(
+        &'a self,
+        _wav_bytes: &'a [u8],
+        _language: &'a str,
+    ) -> BoxFuture<'a, Result<String, AppError>> {
+        let result = self.result.clone().map_err(AppError::Whisper);
+        let delay = self.delay;
+        Box::pin(async move {
+            tokio::time::sleep(delay).await;
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mock(name: &'static str, result: Result<&str, &str>) -> Backend {
+        Backend {
+            transcriber: Box::new(MockTranscriber {
+                name,
+                result: result.map(str::to_string).map_err(str::to_string),
+                delay: Duration::ZERO,
+            }),
+            timeout: None,
+        }
+    }
+
+    #[test]
+    fn chain_starts_with_the_selected_backend() {
+        let engine = Arc::new(WhisperEngine::new());
+        let names = |settings: &AppSettings| -> Vec<(String, Option<Duration>)> {
+            chain_from_settings(settings, &engine)
+                .iter()
+                .map(|b| (b.transcriber.name(), b.timeout))
+                .collect()
+        };
+        let mut settings = AppSettings {
+            whisper_model: "base.en".to_string(),
+            whisper_api_model: "whisper-1".to_string(),
+            whisper_api_timeout_secs: 30,
+            whisper_fallback: true,
+            ..AppSettings::default()
+        };
+        let local = ("base.en".to_string(), None);
+        let api = ("whisper-1 (API)".to_string(), Some(Duration::from_secs(30)));
+
+        // No endpoint, so the API isn't a fallback for local.
+        assert_eq!(names(&settings), vec![local.clone()]);
+
+        settings.whisper_api_endpoint = "http://localhost:8080".to_string();
+        assert_eq!(names(&settings), vec![local.clone(), api.clone()]);
+
+        settings.whisper_mode = WhisperMode::Api;
+        assert_eq!(names(&settings), vec![api.clone(), local]);
+
+        settings.whisper_fallback = false;
+        assert_eq!(names(&settings), vec![api]);
+    }
+
+    #[tokio::test]
+    async fn api_backend_transcribes_through_the_trait() {
+        use crate::test_support::{serve, StubResponse};
+
+        let (url, _requests) = serve(vec![StubResponse::json(200, r#"{"text": " hello "}"#)]).await;
+        let settings = AppSettings {
+            whisper_api_endpoint: url,
+            whisper_api_model: "whisper-1".to_string(),
+            ..AppSettings::default()
+        };
+        let transcriber: Box<dyn Transcriber> =
+            Box::new(ApiWhisper::new(WhisperApiConfig::from_settings(&settings)));
+        let text = transcribe_speech(transcriber.as_ref(), &[], "en").await.unwrap();
+        assert_eq!(text.as_deref(), Some("hello"));
+    }
+
+    #[test]
+    fn overlapping_chunks_are_stitched_once() {
+        let parts = |texts: &[&str]| texts.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            stitch(&parts(&[
+                "so we shipped the new build to the testing team.",
+                "the testing team on Friday and they found",
+                "They found two bugs",
+            ])),
+            "so we shipped the new build to the testing team on Friday and they found two bugs"
+        );
+        // No shared run: nothing is dropped.
+        assert_eq!(stitch(&parts(&["first part.", "Second part"])), "first part. Second part");
+        assert_eq!(stitch(&parts(&["one  piece\nas is"])), "one  piece\nas is");
+    }
+
+    #[tokio::test]
+    async fn speech_is_trimmed() {
+        let chain = [mock("primary", Ok("  hello world \n"))];
+        let t = transcribe_with_fallback(&chain, &[], "en").await.unwrap().unwrap();
+        assert_eq!(t.text, "hello world");
+        assert_eq!(t.backend, "primary");
+    }
+
+    #[tokio::test]
+    async fn silence_ends_the_chain() {
+        let chain = [mock("primary", Ok("   ")), mock("secondary", Ok("hallucinated"))];
+        assert!(transcribe_with_fallback(&chain, &[], "en").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn failure_falls_back_to_next_backend() {
+        let chain = [mock("api", Err("502 Bad Gateway")), mock("local", Ok("hello"))];
+        let t = transcribe_with_fallback(&chain, &[], "en").await.unwrap().unwrap();
+        assert_eq!(t.text, "hello");
+        assert_eq!(t.backend, "local");
+    }
+
+    #[tokio::test]
+    async fn slow_backend_times_out_and_falls_back() {
+        let slow = Backend {
+            transcriber: Box::new(MockTranscriber {
+                name: "api",
+                result: Ok("too late".to_string()),
+                delay: Duration::from_secs(5),
+            }),
+            timeout: Some(Duration::from_millis(20)),
+        };
+        let chain = [slow, mock("local", Ok("on time"))];
+        let t = transcribe_with_fallback(&chain, &[], "en").await.unwrap().unwrap();
+        assert_eq!(t.backend, "local");
+    }
+
+    /// Echoes each chunk as its text, logging the call, and fails on the
+    /// chunk `fails_on`.
+    struct Echo {
+        name: &'static str,
+        fails_on: Option<&'static str>,
+        calls: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    impl Transcriber for Echo {
+        fn name(&self) -> String {
+            self.name.to_string()
+        }
+
+        fn transcribe<'a>(
+            &'a self,
+            wav_bytes: &'a [u8],
+            _language: &'a str,
+        ) -> BoxFuture<'a, Result<String, AppError>> {
+            let text = String::from_utf8_lossy(wav_bytes).to_string();
+            self.calls.lock().unwrap().push(format!("{}: {}", self.name, text));
+            let result = if self.fails_on == Some(text.as_str()) {
+                Err(AppError::Whisper("502 Bad Gateway".to_string()))
+            } else {
+                Ok(text)
+            };
+            Box::pin(async move { result })
+        }
+    }
+
+    #[tokio::test]
+    async fn fallback_resumes_at_the_failed_chunk() {
+        let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let echo = |name, fails_on| Backend {
+            transcriber: Box::new(Echo {
+                name,
+                fails_on,
+                calls: Arc::clone(&calls),
+            }),
+            timeout: None,
+        };
+        let chain = [echo("api", Some("three four")), echo("local", None)];
+        let chunks: Vec<Vec<u8>> = ["one two", "three four", "five six"]
+            .iter()
+            .map(|c| c.as_bytes().to_vec())
+            .collect();
+
+        let t = transcribe_chunks_with_fallback(&chain, &chunks, "en")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(t.text, "one two three four five six");
+        assert_eq!(t.backend, "api, local");
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec!["api: one two", "api: three four", "local: three four", "local: five six"]
+        );
+    }
+
+    #[tokio::test]
+    async fn all_failures_are_reported() {
+        let chain = [mock("api", Err("down")), mock("local", Err("No model loaded"))];
+        let err = transcribe_with_fallback(&chain, &[], "en").await.err().unwrap();
+        let msg = err.to_string();
+        assert!(msg.contains("api: Whisper error: down"), "{msg}");
+        assert!(msg.contains("local: Whisper error: No model loaded"), "{msg}");
+    }
+}
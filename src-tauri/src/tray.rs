@@ -1,3 +1,4 @@
+use std::sync::atomic::{AtomicBool, Ordering};
 use tauri::{
     image::Image,
     menu::{Menu, MenuItem},
@@ -9,6 +10,10 @@ use tauri::{
 const TRAY_ID: &str = "main-tray";
 const ICON_SIZE: u32 = 32;
 
+/// Set while LLM cleanup is being skipped by the circuit breaker; the idle
+/// icon and tooltip reflect it.
+static LLM_DEGRADED: AtomicBool = AtomicBool::new(false);
+
 pub fn setup_tray(app: &App) -> Result<(), Box<dyn std::error::Error>> {
     let show = MenuItem::with_id(app, "show", "Show", true, None::<&str>)?;
     let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
@@ -83,10 +88,15 @@ pub fn set_tray_status(app: &AppHandle, status: &str) {
         return;
     };
 
+    let degraded = LLM_DEGRADED.load(Ordering::Relaxed);
     let (color, tooltip) = match status {
         "recording" => ([220, 38, 38, 255], "Speech AI Tool - Recording..."),
         "processing" => ([234, 179, 8, 255], "Speech AI Tool - Processing..."),
         "done" => ([34, 197, 94, 255], "Speech AI Tool - Done"),
+        _ if degraded => (
+            [180, 120, 60, 255],
+            "Speech AI Tool - LLM cleanup unavailable, pasting raw text",
+        ),
         _ => ([100, 100, 100, 255], "Speech AI Tool"),
     };
 
@@ -94,6 +104,17 @@ pub fn set_tray_status(app: &AppHandle, status: &str) {
     let _ = tray.set_tooltip(Some(tooltip));
 }
 
+/// Record whether LLM cleanup is currently skipped. Takes effect the next
+/// time the tray returns to idle.
+pub fn set_llm_degraded(degraded: bool) {
+    if LLM_DEGRADED.swap(degraded, Ordering::Relaxed) != degraded {
+        eprintln!(
+            "LLM cleanup {}",
+            if degraded { "paused after repeated failures" } else { "recovered" }
+        );
+    }
+}
+
 const OVERLAY_W: f64 = 200.0;
 const OVERLAY_H: f64 = 44.0;
 
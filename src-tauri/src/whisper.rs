@@ -1,10 +1,13 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::io::Cursor;
 use std::path::PathBuf;
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use tauri::{AppHandle, Emitter};
 
 use crate::error::AppError;
+use crate::settings::{AppSettings, WhisperApiProtocol};
 
 const WHISPER_MODELS: &[(&str, &str, &str, &str)] = &[
     ("tiny-q5_1", "32 MB", "ggml-tiny-q5_1.bin", "Fastest — for older or low-end hardware"),
@@ -122,58 +125,139 @@ pub async fn download_model(app: AppHandle, model_name: &str) -> Result<(), AppE
     Ok(())
 }
 
+/// A resident model together with its reusable inference state. Creating a
+/// `WhisperState` allocates the KV cache and compute buffers (hundreds of MB
+/// for the larger models), so we create it once per load instead of per call;
+/// `full()` resets it between runs.
+struct LoadedModel {
+    name: String,
+    // Owns the model weights. The state keeps its own handle to them, but we
+    // hold the context too so dropping `LoadedModel` frees everything at once.
+    _ctx: whisper_rs::WhisperContext,
+    state: whisper_rs::WhisperState,
+}
+
+/// A value kept in memory while it is being used and dropped once it has
+/// sat idle for a while.
+struct Resident<T> {
+    slot: Mutex<Option<T>>,
+    last_used: Mutex<Instant>,
+}
+
+impl<T> Resident<T> {
+    fn new() -> Self {
+        Self {
+            slot: Mutex::new(None),
+            last_used: Mutex::new(Instant::now()),
+        }
+    }
+
+    fn touch(&self) {
+        *self.last_used.lock().unwrap() = Instant::now();
+    }
+
+    /// Take the value out if it has not been used for `idle`.
+    fn take_if_idle(&self, idle: Duration) -> Option<T> {
+        // try_lock: a user in progress holds the lock and is by definition
+        // not idle.
+        let mut guard = self.slot.try_lock().ok()?;
+        if self.last_used.lock().unwrap().elapsed() < idle {
+            return None;
+        }
+        guard.take()
+    }
+}
+
 pub struct WhisperEngine {
-    ctx: Mutex<Option<whisper_rs::WhisperContext>>,
+    loaded: Resident<LoadedModel>,
+    /// The model the user selected. Survives an idle unload so the next
+    /// transcription (or a pre-load on hotkey press) can reload it
+    /// transparently.
+    model_name: Mutex<Option<String>>,
 }
 
 impl WhisperEngine {
     pub fn new() -> Self {
         Self {
-            ctx: Mutex::new(None),
+            loaded: Resident::new(),
+            model_name: Mutex::new(None),
         }
     }
 
+    /// Whether a model is selected and usable. It may currently be unloaded
+    /// after sitting idle; it is reloaded on demand.
     pub fn is_model_loaded(&self) -> bool {
-        self.ctx.lock().unwrap().is_some()
+        self.model_name.lock().unwrap().is_some()
     }
 
     pub fn load_model(&self, model_name: &str) -> Result<(), AppError> {
-        let (_, _, filename, _) = WHISPER_MODELS
-            .iter()
-            .find(|(name, _, _, _)| *name == model_name)
-            .ok_or_else(|| AppError::Whisper(format!("Unknown model: {}", model_name)))?;
-
-        let models_dir = get_models_dir()?;
-        let model_path = models_dir.join(filename);
-
-        if !model_path.exists() {
-            return Err(AppError::Whisper(format!(
-                "Model not downloaded: {}",
-                model_name
-            )));
-        }
+        let loaded = load_from_disk(model_name)?;
+        *self.loaded.slot.lock().unwrap() = Some(loaded);
+        *self.model_name.lock().unwrap() = Some(model_name.to_string());
+        self.loaded.touch();
+        Ok(())
+    }
 
-        let ctx = whisper_rs::WhisperContext::new_with_params(
-            model_path.to_str().unwrap(),
-            whisper_rs::WhisperContextParameters::default(),
-        )
-        .map_err(|e| AppError::Whisper(format!("Failed to load model: {}", e)))?;
+    /// Make `model_name` the selected model without loading it yet; a
+    /// different model already in memory is dropped. Lets the API fallback
+    /// path use the local engine even when local mode never loaded a model.
+    pub fn select_model(&self, model_name: &str) {
+        // Lock order matches `ensure_resident`: `loaded`, then `model_name`.
+        let mut loaded = self.loaded.slot.lock().unwrap();
+        let mut selected = self.model_name.lock().unwrap();
+        if selected.as_deref() == Some(model_name) {
+            return;
+        }
+        if loaded.as_ref().is_some_and(|m| m.name != model_name) {
+            *loaded = None;
+        }
+        *selected = Some(model_name.to_string());
+    }
 
-        *self.ctx.lock().unwrap() = Some(ctx);
+    /// Bring the selected model back into memory if it was unloaded (no-op if
+    /// resident or if no model was ever loaded). Called on hotkey press so the
+    /// reload overlaps with the user speaking.
+    pub fn preload(&self) -> Result<(), AppError> {
+        if self.model_name.lock().unwrap().is_none() {
+            return Ok(());
+        }
+        let mut guard = self.loaded.slot.lock().unwrap();
+        self.ensure_resident(&mut guard)?;
+        self.loaded.touch();
         Ok(())
     }
 
-    pub fn transcribe(&self, wav_bytes: &[u8], language: &str) -> Result<String, AppError> {
-        let guard = self.ctx.lock().unwrap();
-        let ctx = guard
-            .as_ref()
+    /// Drop the model and its state if it has not been used for `idle`.
+    /// The selection is kept, so the next use reloads it. Returns whether
+    /// anything was unloaded.
+    pub fn unload_if_idle(&self, idle: Duration) -> bool {
+        let Some(model) = self.loaded.take_if_idle(idle) else {
+            return false;
+        };
+        eprintln!("Unloading idle whisper model: {}", model.name);
+        true
+    }
+
+    fn ensure_resident(&self, slot: &mut Option<LoadedModel>) -> Result<(), AppError> {
+        if slot.is_some() {
+            return Ok(());
+        }
+        let name = self
+            .model_name
+            .lock()
+            .unwrap()
+            .clone()
             .ok_or_else(|| AppError::Whisper("No model loaded".into()))?;
+        *slot = Some(load_from_disk(&name)?);
+        Ok(())
+    }
 
+    pub fn transcribe(&self, wav_bytes: &[u8], language: &str) -> Result<String, AppError> {
         let samples = decode_wav_to_samples(wav_bytes)?;
 
-        let mut state = ctx
-            .create_state()
-            .map_err(|e| AppError::Whisper(format!("Failed to create state: {}", e)))?;
+        let mut guard = self.loaded.slot.lock().unwrap();
+        self.ensure_resident(&mut guard)?;
+        let model = guard.as_mut().expect("model resident after ensure_resident");
 
         let mut params = whisper_rs::FullParams::new(whisper_rs::SamplingStrategy::Greedy { best_of: 1 });
         params.set_n_threads(num_cpus::get() as i32);
@@ -187,9 +271,12 @@ impl WhisperEngine {
         params.set_print_realtime(false);
         params.set_print_timestamps(false);
 
-        state
+        let state = &mut model.state;
+        let result = state
             .full(params, &samples)
-            .map_err(|e| AppError::Whisper(format!("Transcription failed: {}", e)))?;
+            .map_err(|e| AppError::Whisper(format!("Transcription failed: {}", e)));
+        self.loaded.touch();
+        result?;
 
         let num_segments = state.full_n_segments()
             .map_err(|e| AppError::Whisper(format!("Failed to get segments: {}", e)))?;
@@ -205,68 +292,388 @@ impl WhisperEngine {
     }
 }
 
+fn load_from_disk(model_name: &str) -> Result<LoadedModel, AppError> {
+    let (_, _, filename, _) = WHISPER_MODELS
+        .iter()
+        .find(|(name, _, _, _)| *name == model_name)
+        .ok_or_else(|| AppError::Whisper(format!("Unknown model: {}", model_name)))?;
+
+    let models_dir = get_models_dir()?;
+    let model_path = models_dir.join(filename);
+
+    if !model_path.exists() {
+        return Err(AppError::Whisper(format!(
+            "Model not downloaded: {}",
+            model_name
+        )));
+    }
+
+    let ctx = whisper_rs::WhisperContext::new_with_params(
+        model_path.to_str().unwrap(),
+        whisper_rs::WhisperContextParameters::default(),
+    )
+    .map_err(|e| AppError::Whisper(format!("Failed to load model: {}", e)))?;
+
+    let state = ctx
+        .create_state()
+        .map_err(|e| AppError::Whisper(format!("Failed to create state: {}", e)))?;
+
+    Ok(LoadedModel {
+        name: model_name.to_string(),
+        _ctx: ctx,
+        state,
+    })
+}
+
+/// Connection details for a remote transcription server, gathered from
+/// settings.
+#[derive(Debug, Clone)]
+pub struct WhisperApiConfig {
+    pub endpoint: String,
+    pub api_key: String,
+    pub model: String,
+    pub protocol: WhisperApiProtocol,
+    /// Overrides the protocol's default path when non-empty.
+    pub path: String,
+    pub headers: BTreeMap<String, String>,
+    pub verbose_json: bool,
+}
+
+impl WhisperApiConfig {
+    pub fn from_settings(settings: &AppSettings) -> Self {
+        Self {
+            endpoint: settings.whisper_api_endpoint.clone(),
+            api_key: crate::secrets::usable(&settings.whisper_api_key),
+            model: settings.whisper_api_model.clone(),
+            protocol: settings.whisper_api_protocol.clone(),
+            path: settings.whisper_api_path.clone(),
+            headers: settings.whisper_api_headers.clone(),
+            verbose_json: settings.whisper_api_verbose_json,
+        }
+    }
+
+    fn url(&self) -> String {
+        let path = self.path.trim().trim_start_matches('/');
+        let path = if path.is_empty() {
+            match self.protocol {
+                WhisperApiProtocol::OpenAI => "v1/audio/transcriptions",
+                WhisperApiProtocol::WhisperCpp => "inference",
+            }
+        } else {
+            path
+        };
+        format!("{}/{}", self.endpoint.trim_end_matches('/'), path)
+    }
+}
+
 pub async fn transcribe_via_api(
-    endpoint: &str,
-    api_key: &str,
-    model: &str,
+    config: &WhisperApiConfig,
     wav_bytes: &[u8],
     language: &str,
 ) -> Result<String, AppError> {
-    let url = format!(
-        "{}/v1/audio/transcriptions",
-        endpoint.trim_end_matches('/')
-    );
-
-    // Fall back to the OpenAI default when the field is blank so a cleared
-    // model box still produces a valid request.
-    let model = if model.trim().is_empty() {
-        "whisper-1"
-    } else {
-        model.trim()
-    };
+    let resp = send_transcription_request(&reqwest::Client::new(), config, wav_bytes, language)
+        .await
+        .map_err(|e| AppError::Whisper(format!("API request failed: {}", e)))?;
+
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        return Err(AppError::Whisper(format!("API error {}: {}", status, text)));
+    }
+
+    let body = resp
+        .text()
+        .await
+        .map_err(|e| AppError::Whisper(format!("API request failed: {}", e)))?;
+
+    parse_transcription_response(&body)
+}
+
+async fn send_transcription_request(
+    client: &reqwest::Client,
+    config: &WhisperApiConfig,
+    wav_bytes: &[u8],
+    language: &str,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let url = config.url();
 
     let part = reqwest::multipart::Part::bytes(wav_bytes.to_vec())
         .file_name("audio.wav")
-        .mime_str("audio/wav")
-        .map_err(|e| AppError::Whisper(e.to_string()))?;
+        .mime_str("audio/wav")?;
 
+    let response_format = if config.verbose_json {
+        "verbose_json"
+    } else {
+        "json"
+    };
     let mut form = reqwest::multipart::Form::new()
         .part("file", part)
-        .text("model", model.to_string());
+        .text("response_format", response_format);
+
+    // whisper.cpp's server serves whichever model it was started with and has
+    // no `model` field.
+    if config.protocol == WhisperApiProtocol::OpenAI {
+        // Fall back to the OpenAI default when the field is blank so a cleared
+        // model box still produces a valid request.
+        let model = if config.model.trim().is_empty() {
+            "whisper-1"
+        } else {
+            config.model.trim()
+        };
+        form = form.text("model", model.to_string());
+    }
 
     if language != "auto" {
         form = form.text("language", language.to_string());
     }
 
-    let client = reqwest::Client::new();
     let mut req = client.post(&url).multipart(form);
 
-    if !api_key.is_empty() {
-        req = req.bearer_auth(api_key);
+    if !config.api_key.is_empty() {
+        req = req.bearer_auth(&config.api_key);
+    }
+    for (name, value) in &config.headers {
+        req = req.header(name.as_str(), value.as_str());
     }
 
-    let resp = req
-        .send()
-        .await
-        .map_err(|e| AppError::Whisper(format!("API request failed: {}", e)))?;
+    req.send().await
+}
 
-    if !resp.status().is_success() {
-        let status = resp.status();
-        let text = resp.text().await.unwrap_or_default();
-        return Err(AppError::Whisper(format!("API error {}: {}", status, text)));
+/// Outcome of [`probe_api`], shown by the settings UI's "Test" button.
+#[derive(Debug, Clone, Serialize)]
+pub struct WhisperApiProbe {
+    /// The server answered with an HTTP response at all.
+    pub reachable: bool,
+    /// The server did not reject the API key (401/403).
+    pub auth_ok: bool,
+    /// The server transcribed the sample, so the model name is valid.
+    pub model_accepted: bool,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Send a short generated clip to the configured endpoint and report how far
+/// the request got. Never fails: every problem is described in the result.
+pub async fn probe_api(config: &WhisperApiConfig, language: &str) -> WhisperApiProbe {
+    let mut probe = WhisperApiProbe {
+        reachable: false,
+        auth_ok: false,
+        model_accepted: false,
+        latency_ms: 0,
+        status: None,
+        sample_text: None,
+        error: None,
+    };
+
+    let wav = match probe_wav() {
+        Ok(wav) => wav,
+        Err(e) => {
+            probe.error = Some(e.to_string());
+            return probe;
+        }
+    };
+
+    let client = match reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(PROBE_TIMEOUT_SECS))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            probe.error = Some(e.to_string());
+            return probe;
+        }
+    };
+
+    let started = Instant::now();
+    let result = send_transcription_request(&client, config, &wav, language).await;
+    probe.latency_ms = started.elapsed().as_millis() as u64;
+
+    let resp = match result {
+        Ok(resp) => resp,
+        Err(e) => {
+            probe.error = Some(if e.is_timeout() {
+                format!("No response within {}s", PROBE_TIMEOUT_SECS)
+            } else {
+                format!("Could not connect: {}", e)
+            });
+            return probe;
+        }
+    };
+
+    let status = resp.status();
+    let body = resp.text().await.unwrap_or_default();
+    probe.reachable = true;
+    probe.status = Some(status.as_u16());
+
+    if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+        probe.error = Some(format!("Authentication rejected ({}): {}", status, body));
+        return probe;
     }
+    probe.auth_ok = true;
 
-    #[derive(serde::Deserialize)]
-    struct TranscriptionResponse {
-        text: String,
+    if !status.is_success() {
+        probe.error = Some(if status == reqwest::StatusCode::NOT_FOUND
+            && !body.to_lowercase().contains("model")
+        {
+            format!("Endpoint path not found: {}", config.url())
+        } else {
+            format!("API error {}: {}", status, body)
+        });
+        return probe;
     }
 
-    let parsed: TranscriptionResponse = resp
-        .json()
-        .await
+    match parse_transcription_response(&body) {
+        Ok(text) => {
+            probe.model_accepted = true;
+            probe.sample_text = Some(text);
+        }
+        Err(e) => probe.error = Some(format!("Unexpected response: {}", e)),
+    }
+    probe
+}
+
+const PROBE_TIMEOUT_SECS: u64 = 15;
+
+/// One second of a quiet 440 Hz tone at 16 kHz mono: small, valid, and fast
+/// for any backend to process. The transcription is usually empty.
+fn probe_wav() -> Result<Vec<u8>, AppError> {
+    const RATE: u32 = 16000;
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: RATE,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut buffer = Cursor::new(Vec::new());
+    let mut writer =
+        hound::WavWriter::new(&mut buffer, spec).map_err(|e| AppError::Whisper(e.to_string()))?;
+    for i in 0..RATE {
+        let t = i as f32 / RATE as f32;
+        let sample = (t * 440.0 * std::f32::consts::TAU).sin() * 0.1;
+        writer
+            .write_sample((sample * i16::MAX as f32) as i16)
+            .map_err(|e| AppError::Whisper(e.to_string()))?;
+    }
+    writer
+        .finalize()
+        .map_err(|e| AppError::Whisper(e.to_string()))?;
+    Ok(buffer.into_inner())
+}
+
+#[derive(Deserialize)]
+struct TranscriptionResponse {
+    #[serde(default)]
+    text: String,
+    /// Present with `response_format=verbose_json`.
+    #[serde(default)]
+    segments: Vec<TranscriptionSegment>,
+}
+
+#[derive(Deserialize)]
+struct TranscriptionSegment {
+    text: String,
+    /// OpenAI-style verbose_json only; whisper.cpp omits it.
+    #[serde(default)]
+    no_speech_prob: Option<f64>,
+}
+
+/// Segments the model itself rates as probably silence. Whisper hallucinates
+/// stock phrases ("Thank you.", "Subtitles by...") on quiet audio; verbose
+/// output lets us drop them. Same threshold whisper uses internally.
+const NO_SPEECH_THRESHOLD: f64 = 0.6;
+
+/// Parse a `json` or `verbose_json` transcription body. With segments, the
+/// text is rebuilt from them so likely-silence segments can be dropped.
+fn parse_transcription_response(body: &str) -> Result<String, AppError> {
+    let parsed: TranscriptionResponse = serde_json::from_str(body)
         .map_err(|e| AppError::Whisper(format!("Parse error: {}", e)))?;
 
-    Ok(parsed.text.trim().to_string())
+    if parsed.segments.is_empty() {
+        return Ok(parsed.text.trim().to_string());
+    }
+
+    let text: Vec<&str> = parsed
+        .segments
+        .iter()
+        .filter(|s| s.no_speech_prob.is_none_or(|p| p < NO_SPEECH_THRESHOLD))
+        .map(|s| s.text.trim())
+        .filter(|t| !t.is_empty())
+        .collect();
+
+    Ok(text.join(" "))
+}
+
+/// Length of the recorded audio, or 0 if the WAV can't be read.
+pub fn wav_duration_secs(wav_bytes: &[u8]) -> f64 {
+    match hound::WavReader::new(Cursor::new(wav_bytes)) {
+        Ok(reader) => reader.duration() as f64 / reader.spec().sample_rate as f64,
+        Err(_) => 0.0,
+    }
+}
+
+/// Split a WAV into pieces of `chunk` that overlap by `overlap`, so long
+/// recordings can be transcribed piece by piece. Audio no longer than `chunk`
+/// comes back as a single piece, and a tail shorter than twice the overlap is
+/// added to the last piece instead of becoming one that is mostly overlap.
+pub fn split_wav(wav_bytes: &[u8], chunk: Duration, overlap: Duration) -> Result<Vec<Vec<u8>>, AppError> {
+    let reader = hound::WavReader::new(Cursor::new(wav_bytes))
+        .map_err(|e| AppError::Whisper(format!("Invalid WAV: {}", e)))?;
+    let spec = reader.spec();
+    let frames = |d: Duration| (d.as_secs_f64() * spec.sample_rate as f64) as usize;
+    let (chunk_frames, overlap_frames) = (frames(chunk), frames(overlap));
+    if reader.duration() as usize <= chunk_frames || overlap_frames >= chunk_frames {
+        return Ok(vec![wav_bytes.to_vec()]);
+    }
+
+    let step = (chunk_frames - overlap_frames) * spec.channels as usize;
+    let len = chunk_frames * spec.channels as usize;
+    match spec.sample_format {
+        hound::SampleFormat::Int => split_samples::<i32, _>(reader, spec, len, step),
+        hound::SampleFormat::Float => split_samples::<f32, _>(reader, spec, len, step),
+    }
+}
+
+fn split_samples<S: hound::Sample + Copy, R: std::io::Read>(
+    reader: hound::WavReader<R>,
+    spec: hound::WavSpec,
+    len: usize,
+    step: usize,
+) -> Result<Vec<Vec<u8>>, AppError> {
+    let samples: Vec<S> = reader
+        .into_samples()
+        .collect::<Result<_, _>>()
+        .map_err(|e| AppError::Whisper(format!("Invalid WAV: {}", e)))?;
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    loop {
+        let mut end = (start + len).min(samples.len());
+        if samples.len().saturating_sub(start + step) < 2 * (len - step) {
+            end = samples.len();
+        }
+        let mut buffer = Cursor::new(Vec::new());
+        let mut writer =
+            hound::WavWriter::new(&mut buffer, spec).map_err(|e| AppError::Whisper(e.to_string()))?;
+        for &sample in &samples[start..end] {
+            writer
+                .write_sample(sample)
+                .map_err(|e| AppError::Whisper(e.to_string()))?;
+        }
+        writer
+            .finalize()
+            .map_err(|e| AppError::Whisper(e.to_string()))?;
+        chunks.push(buffer.into_inner());
+
+        if end == samples.len() {
+            return Ok(chunks);
+        }
+        start += step;
+    }
 }
 
 fn decode_wav_to_samples(wav_bytes: &[u8]) -> Result<Vec<f32>, AppError> {
@@ -286,3 +693,155 @@ fn decode_wav_to_samples(wav_bytes: &[u8]) -> Result<Vec<f32>, AppError> {
 
     Ok(samples)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{serve, StubResponse};
+
+    #[test]
+    fn idle_models_are_dropped_but_not_while_in_use() {
+        let resident = Resident::new();
+        *resident.slot.lock().unwrap() = Some("model");
+        let idle = Duration::from_millis(50);
+        assert_eq!(resident.take_if_idle(idle), None);
+
+        std::thread::sleep(idle);
+        {
+            // A transcription holds the slot for as long as it runs.
+            let _in_use = resident.slot.lock().unwrap();
+            assert_eq!(resident.take_if_idle(idle), None);
+        }
+        resident.touch();
+        assert_eq!(resident.take_if_idle(idle), None);
+
+        std::thread::sleep(idle);
+        assert_eq!(resident.take_if_idle(idle), Some("model"));
+        assert_eq!(resident.take_if_idle(idle), None);
+    }
+
+    #[test]
+    fn preload_without_a_selected_model_does_nothing() {
+        let engine = WhisperEngine::new();
+        assert!(engine.preload().is_ok());
+        assert!(!engine.unload_if_idle(Duration::ZERO));
+    }
+
+    #[test]
+    fn wav_duration_is_the_audio_length() {
+        assert_eq!(wav_duration_secs(&probe_wav().unwrap()), 1.0);
+        assert_eq!(wav_duration_secs(b"not a wav"), 0.0);
+    }
+
+    #[test]
+    fn long_audio_is_split_into_overlapping_chunks() {
+        let wav = probe_wav().unwrap();
+        let secs = Duration::from_secs_f64;
+        assert_eq!(split_wav(&wav, secs(1.0), secs(0.1)).unwrap(), vec![wav.clone()]);
+
+        // 1 s in 0.4 s chunks, each starting 0.3 s after the previous.
+        let chunks = split_wav(&wav, secs(0.4), secs(0.1)).unwrap();
+        let durations: Vec<f64> = chunks.iter().map(|c| wav_duration_secs(c)).collect();
+        assert_eq!(durations, vec![0.4, 0.4, 0.4]);
+
+        // A 0.25 s tail would be mostly overlap, so the last chunk takes it.
+        let chunks = split_wav(&wav, secs(0.4), secs(0.15)).unwrap();
+        let durations: Vec<f64> = chunks.iter().map(|c| wav_duration_secs(c)).collect();
+        assert_eq!(durations, vec![0.4, 0.4, 0.5]);
+    }
+
+    fn api_config(endpoint: &str) -> WhisperApiConfig {
+        WhisperApiConfig {
+            endpoint: endpoint.to_string(),
+            api_key: "sk-test".to_string(),
+            model: "whisper-1".to_string(),
+            protocol: WhisperApiProtocol::OpenAI,
+            path: String::new(),
+            headers: BTreeMap::from([("X-Team".to_string(), "speech".to_string())]),
+            verbose_json: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn probe_reports_success_and_sends_credentials() {
+        let (url, requests) = serve(vec![StubResponse::json(200, r#"{"text": "hi"}"#)]).await;
+        let probe = probe_api(&api_config(&url), "en").await;
+
+        assert!(probe.reachable && probe.auth_ok && probe.model_accepted, "{probe:?}");
+        assert_eq!(probe.sample_text.as_deref(), Some("hi"));
+
+        let request = requests.await.unwrap().remove(0).to_lowercase();
+        assert!(request.starts_with("post /v1/audio/transcriptions"));
+        assert!(request.contains("authorization: bearer sk-test"));
+        assert!(request.contains("x-team: speech"));
+        assert!(request.contains("whisper-1"));
+    }
+
+    #[tokio::test]
+    async fn probe_distinguishes_auth_and_model_failures() {
+        let (url, _) = serve(vec![
+            StubResponse::json(401, r#"{"error": "invalid key"}"#),
+            StubResponse::json(404, r#"{"error": "The model `whisper-2` does not exist"}"#),
+        ])
+        .await;
+
+        let probe = probe_api(&api_config(&url), "en").await;
+        assert!(probe.reachable && !probe.auth_ok, "{probe:?}");
+
+        let probe = probe_api(&api_config(&url), "en").await;
+        assert!(probe.auth_ok && !probe.model_accepted, "{probe:?}");
+        assert!(probe.error.unwrap().contains("does not exist"));
+    }
+
+    #[tokio::test]
+    async fn probe_reports_unreachable_server() {
+        // Nothing listens on port 9 (discard) locally.
+        let probe = probe_api(&api_config("http://127.0.0.1:9"), "en").await;
+        assert!(!probe.reachable);
+        assert!(probe.error.is_some());
+    }
+
+    #[test]
+    fn plain_json_uses_text() {
+        let body = r#"{"text": "  Hello there. "}"#;
+        assert_eq!(parse_transcription_response(body).unwrap(), "Hello there.");
+    }
+
+    #[test]
+    fn verbose_json_rebuilds_text_from_segments() {
+        let body = r#"{
+            "text": "Hello there. Thank you.",
+            "segments": [
+                {"start": 0.0, "end": 1.2, "text": " Hello there.", "no_speech_prob": 0.01},
+                {"start": 1.2, "end": 3.0, "text": " Thank you.", "no_speech_prob": 0.92}
+            ]
+        }"#;
+        assert_eq!(parse_transcription_response(body).unwrap(), "Hello there.");
+    }
+
+    #[test]
+    fn whisper_cpp_segments_without_probabilities_are_kept() {
+        let body = r#"{"segments": [{"text": " one"}, {"text": " two"}]}"#;
+        assert_eq!(parse_transcription_response(body).unwrap(), "one two");
+    }
+
+    #[test]
+    fn url_uses_protocol_default_or_override() {
+        let mut config = WhisperApiConfig {
+            endpoint: "http://box:8080/".to_string(),
+            api_key: String::new(),
+            model: String::new(),
+            protocol: WhisperApiProtocol::WhisperCpp,
+            path: String::new(),
+            headers: BTreeMap::new(),
+            verbose_json: false,
+        };
+        assert_eq!(config.url(), "http://box:8080/inference");
+
+        config.protocol = WhisperApiProtocol::OpenAI;
+        assert_eq!(config.url(), "http://box:8080/v1/audio/transcriptions");
+
+        config.path = "/api/v1/transcribe".to_string();
+        assert_eq!(config.url(), "http://box:8080/api/v1/transcribe");
+    }
+}
@@ -4,6 +4,7 @@ import { relaunch } from "@tauri-apps/plugin-process";
 import StatusIndicator from "./components/StatusIndicator";
 import TranscriptionView from "./components/TranscriptionView";
 import HistoryList from "./components/HistoryList";
+import LatencyDashboard from "./components/LatencyDashboard";
 import SettingsPage from "./components/SettingsPage";
 import StatusOverlay from "./components/StatusOverlay";
 import { useAppState } from "./hooks/useAppState";
@@ -30,7 +31,7 @@ export default function App() {
 type Page = "main" | "settings";
 
 function Dashboard() {
-  const { status, rawText, cleanedText, error } = useAppState();
+  const { status, rawText, cleanedText, error, warning } = useAppState();
   const [page, setPage] = useState<Page>("main");
   const [modelLoaded, setModelLoaded] = useState(true);
   const [update, setUpdate] = useState<Update | null>(null);
@@ -184,11 +185,21 @@ function Dashboard() {
             </div>
           )}
 
+          {warning && (
+            <div className="bg-warning/10 text-warning text-sm rounded-lg px-4 py-3">
+              {warning}
+            </div>
+          )}
+
           <TranscriptionView rawText={rawText} cleanedText={cleanedText} />
 
           <div className="bg-surface rounded-lg p-6">
             <HistoryList />
           </div>
+
+          <div className="bg-surface rounded-lg p-6">
+            <LatencyDashboard />
+          </div>
         </div>
       </div>
     </div>
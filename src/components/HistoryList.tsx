@@ -1,10 +1,30 @@
 import { useState } from "react";
 import { useHistory } from "../hooks/useHistory";
-import { copyToClipboard } from "../lib/commands";
+import { copyToClipboard, promoteHistoryItem } from "../lib/commands";
 
 export default function HistoryList() {
   const { records, loading, deleteItem, clearHistory } = useHistory();
   const [expandedId, setExpandedId] = useState<string | null>(null);
+  // Item being promoted to a few-shot example, with the (editable) correction.
+  const [promotingId, setPromotingId] = useState<string | null>(null);
+  const [correction, setCorrection] = useState("");
+  const [promoteStatus, setPromoteStatus] = useState<string | null>(null);
+
+  const startPromote = (id: string, cleanedText: string) => {
+    setPromotingId(id);
+    setCorrection(cleanedText);
+    setPromoteStatus(null);
+  };
+
+  const promote = async (id: string) => {
+    try {
+      await promoteHistoryItem(id, correction);
+      setPromotingId(null);
+      setPromoteStatus("Added to few-shot examples");
+    } catch (e) {
+      setPromoteStatus(`Error: ${String(e)}`);
+    }
+  };
 
   if (loading) {
     return <p className="text-text-muted text-sm">Loading history...</p>;
@@ -67,7 +87,57 @@ export default function HistoryList() {
                     <h4 className="text-xs font-medium text-text-muted mb-1">Cleaned</h4>
                     <p className="text-sm text-text whitespace-pre-wrap">{record.cleaned_text}</p>
                   </div>
+                  {Object.keys(record.timings ?? {}).length > 0 && (
+                    <div>
+                      <h4 className="text-xs font-medium text-text-muted mb-1">Timings</h4>
+                      <p className="text-xs text-text-muted">
+                        {Object.entries(record.timings)
+                          .map(([step, ms]) => `${step} ${ms} ms`)
+                          .join(" · ")}
+                        {record.llm_model && ` (LLM: ${record.llm_model})`}
+                      </p>
+                    </div>
+                  )}
+                  {promotingId === record.id && (
+                    <div>
+                      <h4 className="text-xs font-medium text-text-muted mb-1">
+                        Corrected output
+                      </h4>
+                      <textarea
+                        value={correction}
+                        onChange={(e) => setCorrection(e.target.value)}
+                        rows={3}
+                        className="w-full bg-surface border border-primary rounded px-2 py-1 text-text text-sm focus:outline-none focus:ring-1 focus:ring-accent resize-y"
+                      />
+                    </div>
+                  )}
+                  {promoteStatus && expandedId === record.id && (
+                    <p className="text-xs text-text-muted">{promoteStatus}</p>
+                  )}
                   <div className="flex gap-2">
+                    {promotingId === record.id ? (
+                      <>
+                        <button
+                          onClick={() => promote(record.id)}
+                          className="px-3 py-1 text-xs bg-primary rounded hover:bg-blue-700 transition-colors"
+                        >
+                          Save Example
+                        </button>
+                        <button
+                          onClick={() => setPromotingId(null)}
+                          className="px-3 py-1 text-xs text-text-muted hover:text-text transition-colors"
+                        >
+                          Cancel
+                        </button>
+                      </>
+                    ) : (
+                      <button
+                        onClick={() => startPromote(record.id, record.cleaned_text)}
+                        className="px-3 py-1 text-xs text-text-muted hover:text-text transition-colors"
+                      >
+                        Use as Example
+                      </button>
+                    )}
                     <button
                       onClick={(e) => {
                         e.stopPropagation();
@@ -4,6 +4,7 @@ import { pauseHotkey } from "../lib/commands";
 interface HotkeyInputProps {
   value: string;
   onChange: (hotkey: string) => void;
+  label?: string;
 }
 
 // Map JS KeyboardEvent.code to rdev key names
@@ -122,7 +123,11 @@ function formatHotkeyDisplay(hotkey: string): string {
     .join(" + ");
 }
 
-export default function HotkeyInput({ value, onChange }: HotkeyInputProps) {
+export default function HotkeyInput({
+  value,
+  onChange,
+  label = "Global Hotkey",
+}: HotkeyInputProps) {
   const [editing, setEditing] = useState(false);
   const [heldKeys, setHeldKeys] = useState<Set<string>>(new Set());
 
@@ -176,7 +181,7 @@ export default function HotkeyInput({ value, onChange }: HotkeyInputProps) {
   return (
     <div className="space-y-1">
       <label className="block text-sm font-medium text-text-muted">
-        Global Hotkey
+        {label}
       </label>
       {editing ? (
         <div className="relative">
@@ -206,9 +211,13 @@ export default function HotkeyInput({ value, onChange }: HotkeyInputProps) {
           onClick={startEditing}
           className="w-full text-left bg-bg border border-primary rounded px-3 py-2 text-text text-sm hover:border-accent transition-colors"
         >
-          <kbd className="px-2 py-0.5 bg-primary rounded text-xs">
-            {formatHotkeyDisplay(value)}
-          </kbd>
+          {value ? (
+            <kbd className="px-2 py-0.5 bg-primary rounded text-xs">
+              {formatHotkeyDisplay(value)}
+            </kbd>
+          ) : (
+            <span className="text-text-muted text-xs">Not set</span>
+          )}
           <span className="text-text-muted text-xs ml-2">Click to change</span>
         </button>
       )}
@@ -0,0 +1,73 @@
+import { useState } from "react";
+import { getLatencyStats } from "../lib/commands";
+import type { LatencyStats } from "../lib/types";
+
+// Pipeline steps in the order they run; anything else (custom stages) sorts after.
+const STEP_ORDER = ["stop", "transcribe", "cleanup", "replace", "filter", "script", "output", "total"];
+
+const stepRank = (step: string) => {
+  const idx = STEP_ORDER.indexOf(step);
+  return idx < 0 ? STEP_ORDER.length : idx;
+};
+
+export default function LatencyDashboard() {
+  const [stats, setStats] = useState<LatencyStats[] | null>(null);
+  const [error, setError] = useState<string | null>(null);
+
+  const load = async () => {
+    try {
+      const result = await getLatencyStats();
+      setStats(
+        [...result].sort((a, b) => stepRank(a.step) - stepRank(b.step) || a.model.localeCompare(b.model)),
+      );
+      setError(null);
+    } catch (e) {
+      setError(String(e));
+    }
+  };
+
+  return (
+    <div className="space-y-3">
+      <div className="flex items-center justify-between">
+        <h3 className="text-sm font-medium text-text-muted">Performance</h3>
+        <button
+          onClick={load}
+          className="px-3 py-1 text-xs bg-primary rounded hover:bg-blue-700 transition-colors"
+        >
+          {stats ? "Refresh" : "Show latency"}
+        </button>
+      </div>
+
+      {error && <p className="text-xs text-error">{error}</p>}
+
+      {stats && stats.length === 0 && (
+        <p className="text-text-muted text-sm">No timings recorded yet.</p>
+      )}
+
+      {stats && stats.length > 0 && (
+        <table className="w-full text-xs">
+          <thead>
+            <tr className="text-text-muted text-left">
+              <th className="font-medium pb-1">Step</th>
+              <th className="font-medium pb-1">Model / backend</th>
+              <th className="font-medium pb-1 text-right">Runs</th>
+              <th className="font-medium pb-1 text-right">p50</th>
+              <th className="font-medium pb-1 text-right">p95</th>
+            </tr>
+          </thead>
+          <tbody>
+            {stats.map((s) => (
+              <tr key={`${s.step}/${s.model}`} className="text-text">
+                <td className="py-0.5">{s.step}</td>
+                <td className="py-0.5 text-text-muted">{s.model || "—"}</td>
+                <td className="py-0.5 text-right">{s.count}</td>
+                <td className="py-0.5 text-right">{s.p50_ms} ms</td>
+                <td className="py-0.5 text-right">{s.p95_ms} ms</td>
+              </tr>
+            ))}
+          </tbody>
+        </table>
+      )}
+    </div>
+  );
+}
@@ -1,6 +1,32 @@
-import { useState } from "react";
-import { testLlmConnection } from "../lib/commands";
-import type { AppSettings, LlmConfig, FewShotExample } from "../lib/types";
+import { useCallback, useState } from "react";
+import {
+  exportFewShotExamples,
+  importFewShotExamples,
+  listLlmModels,
+  pullLlmModel,
+  testLlmConnection,
+} from "../lib/commands";
+import { useTauriEvent } from "../hooks/useTauriEvent";
+import type {
+  AppPromptRule,
+  AppSettings,
+  ExampleSetFormat,
+  FewShotSelection,
+  GenerationOptions,
+  LlmApiType,
+  LlmConfig,
+  LlmModelInfo,
+  LlmPullProgress,
+  FewShotExample,
+} from "../lib/types";
+
+const API_TYPES: { value: LlmApiType; label: string }[] = [
+  { value: "ollama", label: "Ollama" },
+  { value: "openai", label: "OpenAI-compatible" },
+  { value: "anthropic", label: "Anthropic" },
+  { value: "llamacpp", label: "llama.cpp" },
+  { value: "lmstudio", label: "LM Studio" },
+];
 
 interface LlmSettingsProps {
   settings: AppSettings;
@@ -13,8 +39,13 @@ export default function LlmSettings({ settings, onChange }: LlmSettingsProps) {
   const [editingIdx, setEditingIdx] = useState<number | null>(null);
   const [editInput, setEditInput] = useState("");
   const [editOutput, setEditOutput] = useState("");
+  const [models, setModels] = useState<LlmModelInfo[]>([]);
+  const [modelStatus, setModelStatus] = useState<string | null>(null);
+  const [pulling, setPulling] = useState(false);
+  const [exampleStatus, setExampleStatus] = useState<string | null>(null);
 
   const examples = settings.llm.few_shot_examples ?? [];
+  const appRules = settings.llm.app_rules ?? [];
 
   const updateLlm = (partial: Partial<LlmConfig>) => {
     onChange({ ...settings, llm: { ...settings.llm, ...partial } });
@@ -24,6 +55,19 @@ export default function LlmSettings({ settings, onChange }: LlmSettingsProps) {
     updateLlm({ few_shot_examples: newExamples });
   };
 
+  const updateOptions = (partial: Partial<GenerationOptions>) => {
+    updateLlm({ options: { ...settings.llm.options, ...partial } });
+  };
+
+  // Empty input = unset (provider default).
+  const optionalNumber = (value: string) => (value === "" ? null : Number(value));
+
+  const updateRule = (idx: number, partial: Partial<AppPromptRule>) => {
+    updateLlm({
+      app_rules: appRules.map((r, i) => (i === idx ? { ...r, ...partial } : r)),
+    });
+  };
+
   const startEdit = (idx: number) => {
     setEditingIdx(idx);
     setEditInput(examples[idx].input);
@@ -54,6 +98,75 @@ export default function LlmSettings({ settings, onChange }: LlmSettingsProps) {
     setEditOutput("");
   };
 
+  const formatOf = (fileName: string): ExampleSetFormat =>
+    /\.ya?ml$/i.test(fileName) ? "yaml" : "json";
+
+  const handleImport = async (file: File, replace: boolean) => {
+    setExampleStatus(null);
+    try {
+      const result = await importFewShotExamples(await file.text(), formatOf(file.name), replace);
+      updateExamples(result.llm.few_shot_examples);
+      setExampleStatus(`Imported ${file.name}`);
+    } catch (e) {
+      setExampleStatus(`Error: ${String(e)}`);
+    }
+  };
+
+  const handleExport = async (format: ExampleSetFormat) => {
+    setExampleStatus(null);
+    try {
+      const contents = await exportFewShotExamples(format);
+      const url = URL.createObjectURL(new Blob([contents], { type: "text/plain" }));
+      const link = document.createElement("a");
+      link.href = url;
+      link.download = `few-shot-examples.${format === "yaml" ? "yaml" : "json"}`;
+      link.click();
+      URL.revokeObjectURL(url);
+    } catch (e) {
+      setExampleStatus(`Error: ${String(e)}`);
+    }
+  };
+
+  const pullHandler = useCallback((event: LlmPullProgress) => {
+    setModelStatus(event.total ? `${event.status} (${event.progress}%)` : event.status);
+  }, []);
+
+  useTauriEvent<LlmPullProgress>("llm-model-pull-progress", pullHandler);
+
+  const handleListModels = async () => {
+    setModelStatus(null);
+    try {
+      const result = await listLlmModels();
+      setModels(result);
+      setModelStatus(`${result.length} model(s) available`);
+    } catch (e) {
+      setModelStatus(`Error: ${String(e)}`);
+    }
+  };
+
+  const handlePull = async () => {
+    setPulling(true);
+    setModelStatus(null);
+    try {
+      await pullLlmModel(settings.llm.model);
+      await handleListModels();
+    } catch (e) {
+      setModelStatus(`Error: ${String(e)}`);
+    } finally {
+      setPulling(false);
+    }
+  };
+
+  const describeModel = (m: LlmModelInfo) =>
+    [
+      m.parameter_size,
+      m.quantization,
+      m.context_length ? `${m.context_length} ctx` : null,
+      m.size_bytes ? `${(m.size_bytes / 1e9).toFixed(1)} GB` : null,
+    ]
+      .filter(Boolean)
+      .join(", ");
+
   const handleTest = async () => {
     setTesting(true);
     setTestResult(null);
@@ -71,27 +184,19 @@ export default function LlmSettings({ settings, onChange }: LlmSettingsProps) {
     <div className="space-y-4">
       <h3 className="text-sm font-medium text-text">LLM Configuration</h3>
 
-      <div className="flex gap-4">
-        <label className="flex items-center gap-2 cursor-pointer">
-          <input
-            type="radio"
-            name="api_type"
-            checked={settings.llm.api_type === "ollama"}
-            onChange={() => updateLlm({ api_type: "ollama" })}
-            className="accent-accent"
-          />
-          <span className="text-sm text-text">Ollama</span>
-        </label>
-        <label className="flex items-center gap-2 cursor-pointer">
-          <input
-            type="radio"
-            name="api_type"
-            checked={settings.llm.api_type === "openai"}
-            onChange={() => updateLlm({ api_type: "openai" })}
-            className="accent-accent"
-          />
-          <span className="text-sm text-text">OpenAI-compatible</span>
-        </label>
+      <div className="flex flex-wrap gap-4">
+        {API_TYPES.map(({ value, label }) => (
+          <label key={value} className="flex items-center gap-2 cursor-pointer">
+            <input
+              type="radio"
+              name="api_type"
+              checked={settings.llm.api_type === value}
+              onChange={() => updateLlm({ api_type: value })}
+              className="accent-accent"
+            />
+            <span className="text-sm text-text">{label}</span>
+          </label>
+        ))}
       </div>
 
       <div className="space-y-3">
@@ -108,15 +213,71 @@ export default function LlmSettings({ settings, onChange }: LlmSettingsProps) {
 
         <div>
           <label className="block text-xs text-text-muted mb-1">Model</label>
+          <div className="flex gap-2">
+            <input
+              type="text"
+              list="llm-models"
+              value={settings.llm.model}
+              onChange={(e) => updateLlm({ model: e.target.value })}
+              placeholder="mistral"
+              className="flex-1 bg-bg border border-primary rounded px-3 py-2 text-text text-sm focus:outline-none focus:ring-1 focus:ring-accent"
+            />
+            <button
+              onClick={handleListModels}
+              className="px-3 py-2 text-xs bg-primary rounded hover:bg-blue-700 transition-colors"
+            >
+              List
+            </button>
+            {settings.llm.api_type === "ollama" && (
+              <button
+                onClick={handlePull}
+                disabled={pulling || !settings.llm.model}
+                className="px-3 py-2 text-xs bg-primary rounded hover:bg-blue-700 disabled:opacity-50 transition-colors"
+              >
+                {pulling ? "Pulling..." : "Pull"}
+              </button>
+            )}
+          </div>
+          <datalist id="llm-models">
+            {models.map((m) => (
+              <option key={m.name} value={m.name}>
+                {describeModel(m)}
+              </option>
+            ))}
+          </datalist>
+          {modelStatus && (
+            <p
+              className={`text-xs mt-1 ${modelStatus.startsWith("Error") ? "text-error" : "text-text-muted"}`}
+            >
+              {modelStatus}
+            </p>
+          )}
+        </div>
+
+        <div>
+          <label className="block text-xs text-text-muted mb-1">API Key</label>
           <input
-            type="text"
-            value={settings.llm.model}
-            onChange={(e) => updateLlm({ model: e.target.value })}
-            placeholder="mistral"
+            type="password"
+            value={settings.llm.api_key.startsWith("keyring:") ? "" : settings.llm.api_key}
+            onChange={(e) => updateLlm({ api_key: e.target.value })}
+            placeholder={settings.llm.api_key.startsWith("keyring:") ? "Saved in the system keyring" : "Leave empty for local servers"}
             className="w-full bg-bg border border-primary rounded px-3 py-2 text-text text-sm focus:outline-none focus:ring-1 focus:ring-accent"
           />
         </div>
 
+        {settings.llm.api_type === "openai" && (
+          <div>
+            <label className="block text-xs text-text-muted mb-1">Organization</label>
+            <input
+              type="text"
+              value={settings.llm.organization}
+              onChange={(e) => updateLlm({ organization: e.target.value })}
+              placeholder="Optional"
+              className="w-full bg-bg border border-primary rounded px-3 py-2 text-text text-sm focus:outline-none focus:ring-1 focus:ring-accent"
+            />
+          </div>
+        )}
+
         <div>
           <label className="block text-xs text-text-muted mb-1">System Prompt</label>
           <textarea
@@ -127,19 +288,247 @@ export default function LlmSettings({ settings, onChange }: LlmSettingsProps) {
           />
         </div>
 
-        {/* Few-shot examples */}
+        {/* Generation options */}
+        <div>
+          <label className="block text-xs text-text-muted mb-2">Generation Options</label>
+          <div className="grid grid-cols-3 gap-2">
+            <div>
+              <label className="block text-xs text-text-muted mb-1">Temperature</label>
+              <input
+                type="number"
+                min={0}
+                max={settings.llm.api_type === "anthropic" ? 1 : 2}
+                step={0.1}
+                value={settings.llm.options.temperature}
+                onChange={(e) => updateOptions({ temperature: Number(e.target.value) })}
+                className="w-full bg-bg border border-primary rounded px-2 py-1 text-text text-sm focus:outline-none focus:ring-1 focus:ring-accent"
+              />
+            </div>
+            <div>
+              <label className="block text-xs text-text-muted mb-1">Top P</label>
+              <input
+                type="number"
+                min={0}
+                max={1}
+                step={0.05}
+                value={settings.llm.options.top_p ?? ""}
+                placeholder="default"
+                onChange={(e) => updateOptions({ top_p: optionalNumber(e.target.value) })}
+                className="w-full bg-bg border border-primary rounded px-2 py-1 text-text text-sm focus:outline-none focus:ring-1 focus:ring-accent"
+              />
+            </div>
+            <div>
+              <label className="block text-xs text-text-muted mb-1">Max tokens</label>
+              <input
+                type="number"
+                min={1}
+                value={settings.llm.options.max_tokens ?? ""}
+                placeholder="default"
+                onChange={(e) => updateOptions({ max_tokens: optionalNumber(e.target.value) })}
+                className="w-full bg-bg border border-primary rounded px-2 py-1 text-text text-sm focus:outline-none focus:ring-1 focus:ring-accent"
+              />
+            </div>
+            {settings.llm.api_type !== "anthropic" && (
+              <div>
+                <label className="block text-xs text-text-muted mb-1">Seed</label>
+                <input
+                  type="number"
+                  value={settings.llm.options.seed ?? ""}
+                  placeholder="random"
+                  onChange={(e) => updateOptions({ seed: optionalNumber(e.target.value) })}
+                  className="w-full bg-bg border border-primary rounded px-2 py-1 text-text text-sm focus:outline-none focus:ring-1 focus:ring-accent"
+                />
+              </div>
+            )}
+            {settings.llm.api_type === "ollama" && (
+              <>
+                <div>
+                  <label className="block text-xs text-text-muted mb-1">Context size</label>
+                  <input
+                    type="number"
+                    min={1}
+                    value={settings.llm.options.num_ctx ?? ""}
+                    placeholder="default"
+                    onChange={(e) => updateOptions({ num_ctx: optionalNumber(e.target.value) })}
+                    className="w-full bg-bg border border-primary rounded px-2 py-1 text-text text-sm focus:outline-none focus:ring-1 focus:ring-accent"
+                  />
+                </div>
+                <div>
+                  <label className="block text-xs text-text-muted mb-1">Keep alive</label>
+                  <input
+                    type="text"
+                    value={settings.llm.options.keep_alive}
+                    placeholder="e.g. 30m, -1"
+                    onChange={(e) => updateOptions({ keep_alive: e.target.value })}
+                    className="w-full bg-bg border border-primary rounded px-2 py-1 text-text text-sm focus:outline-none focus:ring-1 focus:ring-accent"
+                  />
+                </div>
+              </>
+            )}
+          </div>
+          {settings.llm.api_type === "ollama" && (
+            <label className="flex items-center gap-2 cursor-pointer mt-2">
+              <input
+                type="checkbox"
+                checked={settings.llm.options.think}
+                onChange={(e) => updateOptions({ think: e.target.checked })}
+                className="accent-accent"
+              />
+              <span className="text-sm text-text">Allow thinking (slower)</span>
+            </label>
+          )}
+          {settings.llm.api_type === "ollama" && (
+            <label className="flex items-center gap-2 cursor-pointer mt-2">
+              <input
+                type="checkbox"
+                checked={settings.llm.warm_up}
+                onChange={(e) => updateLlm({ warm_up: e.target.checked })}
+                className="accent-accent"
+              />
+              <span className="text-sm text-text">Load the model when the hotkey is pressed</span>
+            </label>
+          )}
+        </div>
+
+        {settings.rewrite_hotkey && (
+          <div>
+            <label className="block text-xs text-text-muted mb-1">Rewrite Prompt</label>
+            <textarea
+              value={settings.llm.rewrite_prompt}
+              onChange={(e) => updateLlm({ rewrite_prompt: e.target.value })}
+              rows={4}
+              className="w-full bg-bg border border-primary rounded px-3 py-2 text-text text-sm focus:outline-none focus:ring-1 focus:ring-accent resize-y"
+            />
+          </div>
+        )}
+
+        <label className="flex items-center gap-2 cursor-pointer">
+          <input
+            type="checkbox"
+            checked={settings.llm.app_context}
+            onChange={(e) => updateLlm({ app_context: e.target.checked })}
+            className="accent-accent"
+          />
+          <span className="text-sm text-text">
+            Tell the LLM which application the text is going into
+          </span>
+        </label>
+
+        {/* Per-application prompts */}
         <div>
           <div className="flex items-center justify-between mb-2">
             <label className="block text-xs text-text-muted">
-              Few-Shot Examples ({examples.length})
+              Application Prompts ({appRules.length})
             </label>
             <button
-              onClick={addExample}
+              onClick={() =>
+                updateLlm({
+                  app_rules: [
+                    ...appRules,
+                    { pattern: "", system_prompt: settings.llm.system_prompt },
+                  ],
+                })
+              }
               className="px-2 py-0.5 text-xs bg-primary rounded hover:bg-blue-700 transition-colors"
             >
               + Add
             </button>
           </div>
+          <div className="space-y-2">
+            {appRules.map((rule, idx) => (
+              <div key={idx} className="bg-bg rounded p-3 border border-primary/20 space-y-2">
+                <div className="flex gap-2">
+                  <input
+                    type="text"
+                    value={rule.pattern}
+                    onChange={(e) => updateRule(idx, { pattern: e.target.value })}
+                    placeholder="App name or window title contains..."
+                    className="flex-1 bg-surface border border-primary rounded px-2 py-1 text-text text-xs focus:outline-none focus:ring-1 focus:ring-accent"
+                  />
+                  <button
+                    onClick={() => updateLlm({ app_rules: appRules.filter((_, i) => i !== idx) })}
+                    className="px-2 py-0.5 text-xs text-error hover:bg-error/10 rounded transition-colors"
+                  >
+                    Delete
+                  </button>
+                </div>
+                <textarea
+                  value={rule.system_prompt}
+                  onChange={(e) => updateRule(idx, { system_prompt: e.target.value })}
+                  rows={3}
+                  className="w-full bg-surface border border-primary rounded px-2 py-1 text-text text-xs focus:outline-none focus:ring-1 focus:ring-accent resize-y"
+                />
+              </div>
+            ))}
+          </div>
+        </div>
+
+        {/* Few-shot examples */}
+        <div>
+          <div className="flex items-center justify-between mb-2">
+            <label className="block text-xs text-text-muted">
+              Few-Shot Examples ({examples.length})
+            </label>
+            <div className="flex items-center gap-2">
+              <label className="px-2 py-0.5 text-xs text-text-muted hover:text-text cursor-pointer transition-colors">
+                Import
+                <input
+                  type="file"
+                  accept=".json,.yaml,.yml"
+                  className="hidden"
+                  onChange={(e) => {
+                    const file = e.target.files?.[0];
+                    if (file) handleImport(file, false);
+                    e.target.value = "";
+                  }}
+                />
+              </label>
+              <button
+                onClick={() => handleExport("json")}
+                className="px-2 py-0.5 text-xs text-text-muted hover:text-text transition-colors"
+              >
+                Export JSON
+              </button>
+              <button
+                onClick={() => handleExport("yaml")}
+                className="px-2 py-0.5 text-xs text-text-muted hover:text-text transition-colors"
+              >
+                Export YAML
+              </button>
+              <button
+                onClick={addExample}
+                className="px-2 py-0.5 text-xs bg-primary rounded hover:bg-blue-700 transition-colors"
+              >
+                + Add
+              </button>
+            </div>
+          </div>
+          {exampleStatus && (
+            <p className="text-xs text-text-muted mb-2">{exampleStatus}</p>
+          )}
+          <div className="flex items-center gap-4 mb-2">
+            <label className="flex items-center gap-2 text-xs text-text-muted">
+              Send at most
+              <input
+                type="number"
+                min={0}
+                value={settings.llm.few_shot_limit}
+                onChange={(e) => updateLlm({ few_shot_limit: Number(e.target.value) })}
+                className="w-16 bg-bg border border-primary rounded px-2 py-1 text-text text-sm focus:outline-none focus:ring-1 focus:ring-accent"
+              />
+              (0 = all)
+            </label>
+            <select
+              value={settings.llm.few_shot_selection}
+              onChange={(e) =>
+                updateLlm({ few_shot_selection: e.target.value as FewShotSelection })
+              }
+              className="bg-bg border border-primary rounded px-2 py-1 text-text text-xs focus:outline-none focus:ring-1 focus:ring-accent"
+            >
+              <option value="similar">Most similar to the transcription</option>
+              <option value="first">First in the list</option>
+            </select>
+          </div>
           <div className="space-y-2 max-h-80 overflow-y-auto">
             {examples.map((ex, idx) => (
               <div key={idx} className="bg-bg rounded p-3 border border-primary/20">
@@ -0,0 +1,340 @@
+import type { AppSettings, PipelineProfile, ReplaceRule, StageConfig } from "../lib/types";
+
+const STAGE_TYPES: { value: StageConfig["type"]; label: string; description: string }[] = [
+  { value: "cleanup", label: "LLM cleanup", description: "Clean up the text with the LLM settings above" },
+  { value: "replace", label: "Replace", description: "Replace words or phrases" },
+  { value: "filter", label: "Filter", description: "Drop short dictations or known noise" },
+  {
+    value: "script",
+    label: "Script",
+    description: "Pipe the text to a command and use what it prints",
+  },
+  { value: "output", label: "Output", description: "Copy and paste into the focused app" },
+  { value: "history", label: "History", description: "Save to history" },
+];
+
+const DEFAULT_STAGES: StageConfig[] = [{ type: "cleanup" }, { type: "output" }, { type: "history" }];
+
+function newStage(type: StageConfig["type"]): StageConfig {
+  switch (type) {
+    case "replace":
+      return { type, rules: [] };
+    case "filter":
+      return { type, min_words: 1, ignore: [] };
+    case "script":
+      return { type, command: "", args: [], timeout_secs: 10 };
+    default:
+      return { type };
+  }
+}
+
+interface PipelineSettingsProps {
+  settings: AppSettings;
+  onChange: (settings: AppSettings) => void;
+}
+
+export default function PipelineSettings({ settings, onChange }: PipelineSettingsProps) {
+  const profiles = settings.pipeline_profiles ?? [];
+  const activeIdx = profiles.findIndex((p) => p.name === settings.active_profile);
+  const active: PipelineProfile = profiles[activeIdx] ?? {
+    name: settings.active_profile,
+    stages: DEFAULT_STAGES,
+  };
+
+  const updateProfiles = (newProfiles: PipelineProfile[], activeName = settings.active_profile) => {
+    onChange({ ...settings, pipeline_profiles: newProfiles, active_profile: activeName });
+  };
+
+  const updateActive = (partial: Partial<PipelineProfile>) => {
+    const updated = { ...active, ...partial };
+    const newProfiles =
+      activeIdx >= 0 ? profiles.map((p, i) => (i === activeIdx ? updated : p)) : [...profiles, updated];
+    updateProfiles(newProfiles, updated.name);
+  };
+
+  const updateStage = (idx: number, stage: StageConfig) => {
+    updateActive({ stages: active.stages.map((s, i) => (i === idx ? stage : s)) });
+  };
+
+  const moveStage = (idx: number, delta: number) => {
+    const target = idx + delta;
+    if (target < 0 || target >= active.stages.length) return;
+    const stages = [...active.stages];
+    [stages[idx], stages[target]] = [stages[target], stages[idx]];
+    updateActive({ stages });
+  };
+
+  const addProfile = () => {
+    let name = "New profile";
+    for (let n = 2; profiles.some((p) => p.name === name); n++) name = `New profile ${n}`;
+    updateProfiles([...profiles, { name, stages: DEFAULT_STAGES }], name);
+  };
+
+  const deleteProfile = () => {
+    const remaining = profiles.filter((_, i) => i !== activeIdx);
+    updateProfiles(remaining, remaining[0]?.name ?? "Default");
+  };
+
+  // New processing stages go before the output and history, where they
+  // still affect what is pasted.
+  const addStage = (type: StageConfig["type"]) => {
+    const stage = newStage(type);
+    const stages = [...active.stages];
+    const firstSink = stages.findIndex((s) => s.type === "output" || s.type === "history");
+    if (type === "output" || type === "history" || firstSink < 0) {
+      stages.push(stage);
+    } else {
+      stages.splice(firstSink, 0, stage);
+    }
+    updateActive({ stages });
+  };
+
+  const updateRules = (idx: number, rules: ReplaceRule[]) => {
+    updateStage(idx, { type: "replace", rules });
+  };
+
+  return (
+    <div className="space-y-4">
+      <h3 className="text-sm font-medium text-text">Pipeline</h3>
+      <p className="text-xs text-text-muted">
+        What happens to a dictation after it is transcribed, in order. Profiles let you keep
+        several pipelines and switch between them.
+      </p>
+
+      <div className="flex items-end gap-2">
+        <div>
+          <label className="block text-xs text-text-muted mb-1">Active profile</label>
+          <select
+            value={settings.active_profile}
+            onChange={(e) => updateProfiles(profiles, e.target.value)}
+            className="bg-bg border border-primary rounded px-3 py-2 text-text text-sm focus:outline-none focus:ring-1 focus:ring-accent"
+          >
+            {profiles.map((p) => (
+              <option key={p.name} value={p.name}>
+                {p.name}
+              </option>
+            ))}
+          </select>
+        </div>
+        <div>
+          <label className="block text-xs text-text-muted mb-1">Name</label>
+          <input
+            type="text"
+            value={active.name}
+            onChange={(e) => updateActive({ name: e.target.value })}
+            className="w-40 bg-bg border border-primary rounded px-3 py-2 text-text text-sm focus:outline-none focus:ring-1 focus:ring-accent"
+          />
+        </div>
+        <button
+          onClick={addProfile}
+          className="px-2 py-2 text-xs bg-primary rounded hover:bg-blue-700 transition-colors"
+        >
+          + Profile
+        </button>
+        {profiles.length > 1 && (
+          <button
+            onClick={deleteProfile}
+            className="px-2 py-2 text-xs text-error hover:bg-error/10 rounded transition-colors"
+          >
+            Delete
+          </button>
+        )}
+      </div>
+
+      <div className="space-y-2">
+        {active.stages.map((stage, idx) => {
+          const info = STAGE_TYPES.find((t) => t.value === stage.type);
+          return (
+            <div key={idx} className="bg-bg rounded p-3 border border-primary/20 space-y-2">
+              <div className="flex items-center justify-between">
+                <div>
+                  <span className="text-sm text-text">
+                    {idx + 1}. {info?.label ?? stage.type}
+                  </span>
+                  <span className="text-xs text-text-muted ml-2">{info?.description}</span>
+                </div>
+                <div className="flex gap-1">
+                  <button
+                    onClick={() => moveStage(idx, -1)}
+                    className="px-1 text-xs text-text-muted hover:text-text"
+                  >
+                    ▲
+                  </button>
+                  <button
+                    onClick={() => moveStage(idx, 1)}
+                    className="px-1 text-xs text-text-muted hover:text-text"
+                  >
+                    ▼
+                  </button>
+                  <button
+                    onClick={() => updateActive({ stages: active.stages.filter((_, i) => i !== idx) })}
+                    className="px-2 text-xs text-error hover:bg-error/10 rounded transition-colors"
+                  >
+                    Remove
+                  </button>
+                </div>
+              </div>
+
+              {stage.type === "replace" && (
+                <div className="space-y-1">
+                  {stage.rules.map((rule, ruleIdx) => (
+                    <div key={ruleIdx} className="flex items-center gap-2">
+                      <input
+                        type="text"
+                        value={rule.find}
+                        placeholder="find"
+                        onChange={(e) =>
+                          updateRules(
+                            idx,
+                            stage.rules.map((r, i) => (i === ruleIdx ? { ...r, find: e.target.value } : r)),
+                          )
+                        }
+                        className="flex-1 bg-surface border border-primary rounded px-2 py-1 text-text text-xs focus:outline-none focus:ring-1 focus:ring-accent"
+                      />
+                      <input
+                        type="text"
+                        value={rule.replace}
+                        placeholder="replace with"
+                        onChange={(e) =>
+                          updateRules(
+                            idx,
+                            stage.rules.map((r, i) => (i === ruleIdx ? { ...r, replace: e.target.value } : r)),
+                          )
+                        }
+                        className="flex-1 bg-surface border border-primary rounded px-2 py-1 text-text text-xs focus:outline-none focus:ring-1 focus:ring-accent"
+                      />
+                      <label className="flex items-center gap-1 text-xs text-text-muted">
+                        <input
+                          type="checkbox"
+                          checked={rule.case_sensitive}
+                          onChange={(e) =>
+                            updateRules(
+                              idx,
+                              stage.rules.map((r, i) =>
+                                i === ruleIdx ? { ...r, case_sensitive: e.target.checked } : r,
+                              ),
+                            )
+                          }
+                          className="accent-accent"
+                        />
+                        Aa
+                      </label>
+                      <button
+                        onClick={() => updateRules(idx, stage.rules.filter((_, i) => i !== ruleIdx))}
+                        className="px-1 text-xs text-error"
+                      >
+                        ✕
+                      </button>
+                    </div>
+                  ))}
+                  <button
+                    onClick={() =>
+                      updateRules(idx, [...stage.rules, { find: "", replace: "", case_sensitive: false }])
+                    }
+                    className="px-2 py-0.5 text-xs text-text-muted hover:text-text transition-colors"
+                  >
+                    + Rule
+                  </button>
+                </div>
+              )}
+
+              {stage.type === "filter" && (
+                <div className="flex gap-3">
+                  <div>
+                    <label className="block text-xs text-text-muted mb-1">Min words</label>
+                    <input
+                      type="number"
+                      min={0}
+                      value={stage.min_words}
+                      onChange={(e) => updateStage(idx, { ...stage, min_words: Number(e.target.value) })}
+                      className="w-20 bg-surface border border-primary rounded px-2 py-1 text-text text-xs focus:outline-none focus:ring-1 focus:ring-accent"
+                    />
+                  </div>
+                  <div className="flex-1">
+                    <label className="block text-xs text-text-muted mb-1">
+                      Ignore these phrases (one per line)
+                    </label>
+                    <textarea
+                      value={stage.ignore.join("\n")}
+                      onChange={(e) => updateStage(idx, { ...stage, ignore: e.target.value.split("\n") })}
+                      rows={2}
+                      className="w-full bg-surface border border-primary rounded px-2 py-1 text-text text-xs focus:outline-none focus:ring-1 focus:ring-accent resize-y"
+                    />
+                  </div>
+                </div>
+              )}
+
+              {stage.type === "script" && (
+                <div className="space-y-2">
+                  <div className="flex gap-3">
+                    <div className="flex-1">
+                      <label className="block text-xs text-text-muted mb-1">Command</label>
+                      <input
+                        type="text"
+                        value={stage.command}
+                        placeholder="/path/to/script"
+                        onChange={(e) => updateStage(idx, { ...stage, command: e.target.value })}
+                        className="w-full bg-surface border border-primary rounded px-2 py-1 text-text text-xs focus:outline-none focus:ring-1 focus:ring-accent"
+                      />
+                    </div>
+                    <div>
+                      <label className="block text-xs text-text-muted mb-1">Timeout (s)</label>
+                      <input
+                        type="number"
+                        min={1}
+                        value={stage.timeout_secs}
+                        onChange={(e) => updateStage(idx, { ...stage, timeout_secs: Number(e.target.value) })}
+                        className="w-20 bg-surface border border-primary rounded px-2 py-1 text-text text-xs focus:outline-none focus:ring-1 focus:ring-accent"
+                      />
+                    </div>
+                  </div>
+                  <div>
+                    <label className="block text-xs text-text-muted mb-1">
+                      Arguments (one per line)
+                    </label>
+                    <textarea
+                      value={stage.args.join("\n")}
+                      onChange={(e) =>
+                        updateStage(idx, {
+                          ...stage,
+                          args: e.target.value === "" ? [] : e.target.value.split("\n"),
+                        })
+                      }
+                      rows={2}
+                      className="w-full bg-surface border border-primary rounded px-2 py-1 text-text text-xs focus:outline-none focus:ring-1 focus:ring-accent resize-y"
+                    />
+                  </div>
+                  <p className="text-xs text-text-muted">
+                    Receives <code>{"{text, raw_text, backend, duration_secs, app}"}</code> as JSON on
+                    stdin and prints the new text. Printing nothing drops the dictation; if the command
+                    fails or times out, the text is kept as it was.
+                  </p>
+                </div>
+              )}
+            </div>
+          );
+        })}
+        {!active.stages.some((s) => s.type === "output") && (
+          <p className="text-xs text-warning">
+            This profile has no Output stage, so nothing will be pasted.
+          </p>
+        )}
+      </div>
+
+      <select
+        value=""
+        onChange={(e) => {
+          if (e.target.value) addStage(e.target.value as StageConfig["type"]);
+        }}
+        className="bg-bg border border-primary rounded px-3 py-2 text-text text-sm focus:outline-none focus:ring-1 focus:ring-accent"
+      >
+        <option value="">+ Add stage...</option>
+        {STAGE_TYPES.map((t) => (
+          <option key={t.value} value={t.value}>
+            {t.label}
+          </option>
+        ))}
+      </select>
+    </div>
+  );
+}
@@ -4,6 +4,7 @@ import AudioDeviceSelect from "./AudioDeviceSelect";
 import HotkeyInput from "./HotkeyInput";
 import WhisperSettings from "./WhisperSettings";
 import LlmSettings from "./LlmSettings";
+import PipelineSettings from "./PipelineSettings";
 import type { AppSettings } from "../lib/types";
 
 interface SettingsPageProps {
@@ -44,6 +45,22 @@ export default function SettingsPage({ onBack }: SettingsPageProps) {
         <AudioDeviceSelect
           onDeviceChange={(idx) => update({ audio_device_index: idx })}
         />
+        <div className="mt-3">
+          <label className="block text-xs text-text-muted mb-1">
+            Maximum recording length (minutes, 0 = no limit)
+          </label>
+          <input
+            type="number"
+            min={0}
+            step={1}
+            value={settings.max_recording_secs / 60}
+            onChange={(e) => update({ max_recording_secs: Math.round(Number(e.target.value) * 60) })}
+            className="w-24 bg-bg border border-primary rounded px-3 py-2 text-text text-sm focus:outline-none focus:ring-1 focus:ring-accent"
+          />
+          <p className="text-xs text-text-muted mt-1">
+            A tone plays 10 seconds before the recording stops on its own.
+          </p>
+        </div>
       </section>
 
       {/* Hotkey */}
@@ -52,6 +69,38 @@ export default function SettingsPage({ onBack }: SettingsPageProps) {
           value={settings.hotkey}
           onChange={(hotkey) => update({ hotkey })}
         />
+        <div className="mt-3 space-y-2">
+          <HotkeyInput
+            label="Rewrite Hotkey"
+            value={settings.rewrite_hotkey}
+            onChange={(rewrite_hotkey) => update({ rewrite_hotkey })}
+          />
+          <p className="text-xs text-text-muted">
+            Select text, hold this and speak an instruction ("make this more formal") to
+            rewrite the selection.
+          </p>
+          {settings.rewrite_hotkey && (
+            <div className="flex items-end gap-3">
+              <div>
+                <label className="block text-xs text-text-muted mb-1">
+                  Copy shortcut (e.g. Ctrl+C, Cmd+C)
+                </label>
+                <input
+                  type="text"
+                  value={settings.copy_shortcut}
+                  onChange={(e) => update({ copy_shortcut: e.target.value })}
+                  className="w-48 bg-bg border border-primary rounded px-3 py-2 text-text text-sm focus:outline-none focus:ring-1 focus:ring-accent"
+                />
+              </div>
+              <button
+                onClick={() => update({ rewrite_hotkey: "" })}
+                className="px-3 py-2 text-xs text-error hover:bg-error/10 rounded transition-colors"
+              >
+                Disable
+              </button>
+            </div>
+          )}
+        </div>
       </section>
 
       {/* Whisper */}
@@ -70,6 +119,14 @@ export default function SettingsPage({ onBack }: SettingsPageProps) {
         />
       </section>
 
+      {/* Pipeline */}
+      <section className="bg-surface rounded-lg p-4">
+        <PipelineSettings
+          settings={settings}
+          onChange={saveSettings}
+        />
+      </section>
+
       {/* Output */}
       <section className="bg-surface rounded-lg p-4">
         <h3 className="text-sm font-medium text-text mb-3">Output</h3>
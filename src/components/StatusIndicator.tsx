@@ -3,6 +3,7 @@ import type { PipelineStatus } from "../lib/types";
 const statusConfig: Record<PipelineStatus, { color: string; label: string }> = {
   idle: { color: "bg-text-muted", label: "Idle" },
   recording: { color: "bg-recording animate-pulse", label: "Recording..." },
+  queued: { color: "bg-processing", label: "Queued..." },
   transcribing: { color: "bg-processing animate-pulse", label: "Transcribing..." },
   cleaning: { color: "bg-processing animate-pulse", label: "Cleaning up..." },
   done: { color: "bg-success", label: "Done" },
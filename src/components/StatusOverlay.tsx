@@ -1,4 +1,4 @@
-import { useState, useCallback, useEffect } from "react";
+import { useState, useCallback, useEffect, useRef } from "react";
 import { getCurrentWindow } from "@tauri-apps/api/window";
 import { useTauriEvent } from "../hooks/useTauriEvent";
 import type { PipelineStatus, PipelineStatusEvent } from "../lib/types";
@@ -6,17 +6,29 @@ import type { PipelineStatus, PipelineStatusEvent } from "../lib/types";
 export default function StatusOverlay() {
   const [status, setStatus] = useState<PipelineStatus>("recording");
   const [visible, setVisible] = useState(true);
+  const [level, setLevel] = useState(0);
+  const [queueDepth, setQueueDepth] = useState(0);
+  const runId = useRef<string | null>(null);
 
   useEffect(() => {
     document.documentElement.classList.add("overlay-window");
   }, []);
 
   const handler = useCallback((event: PipelineStatusEvent) => {
+    // A new recording starts a new run; late events of earlier runs are stale.
+    if (event.status === "recording") {
+      if (event.run_id !== runId.current) setLevel(0);
+      runId.current = event.run_id;
+    } else if (event.run_id !== runId.current) {
+      return;
+    }
+    if (event.level !== undefined) setLevel(event.level);
     setStatus(event.status);
     setVisible(true);
   }, []);
 
   useTauriEvent<PipelineStatusEvent>("pipeline-status", handler);
+  useTauriEvent<number>("pipeline-queue", setQueueDepth);
 
   useEffect(() => {
     if (status === "done") {
@@ -37,9 +49,13 @@ export default function StatusOverlay() {
 
   if (!visible) return null;
 
+  // Runs in the queue other than the one shown; a recording isn't queued yet.
+  const waiting = status === "recording" ? queueDepth : Math.max(0, queueDepth - 1);
+
   const label: Record<PipelineStatus, string> = {
     idle: "",
     recording: "Listening…",
+    queued: "Queued…",
     transcribing: "Transcribing…",
     cleaning: "Cleaning up…",
     done: "Done",
@@ -59,7 +75,7 @@ export default function StatusOverlay() {
           </rect>
         </svg>
       )}
-      {(status === "transcribing" || status === "cleaning") && (
+      {(status === "queued" || status === "transcribing" || status === "cleaning") && (
         <svg className="w-4 h-4 shrink-0 animate-spin" viewBox="0 0 24 24" fill="none">
           <circle className="opacity-25" cx="12" cy="12" r="10" stroke="#eab308" strokeWidth="3" />
           <path fill="#eab308" opacity="0.8" d="M4 12a8 8 0 018-8v4a4 4 0 00-4 4H4z" />
@@ -77,6 +93,18 @@ export default function StatusOverlay() {
         </svg>
       )}
       <span className="text-sm font-medium text-white whitespace-nowrap">{label[status]}</span>
+      {status === "recording" && (
+        <div className="w-10 h-1.5 shrink-0 rounded-full bg-white/15 overflow-hidden">
+          {/* Speech is rarely above 0.3 RMS; scale so it fills most of the bar. */}
+          <div
+            className="h-full bg-red-400 transition-[width] duration-100"
+            style={{ width: `${Math.min(100, Math.sqrt(level / 0.3) * 100)}%` }}
+          />
+        </div>
+      )}
+      {waiting > 0 && (
+        <span className="text-xs text-white/60 whitespace-nowrap">+{waiting} queued</span>
+      )}
     </div>
   );
 }
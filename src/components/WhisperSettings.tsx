@@ -1,12 +1,55 @@
-import type { AppSettings } from "../lib/types";
+import { useState } from "react";
+import { testWhisperApi } from "../lib/commands";
+import type { AppSettings, WhisperApiProbe } from "../lib/types";
 import ModelDownload from "./ModelDownload";
 
+function formatHeaders(headers: Record<string, string>): string {
+  return Object.entries(headers)
+    .map(([name, value]) => `${name}: ${value}`)
+    .join("\n");
+}
+
+function parseHeaders(text: string): Record<string, string> {
+  const headers: Record<string, string> = {};
+  for (const line of text.split("\n")) {
+    const colon = line.indexOf(":");
+    if (colon <= 0) continue;
+    headers[line.slice(0, colon).trim()] = line.slice(colon + 1).trim();
+  }
+  return headers;
+}
+
 interface WhisperSettingsProps {
   settings: AppSettings;
   onChange: (settings: AppSettings) => void;
 }
 
 export default function WhisperSettings({ settings, onChange }: WhisperSettingsProps) {
+  // Edited as text and parsed on blur, so half-typed lines aren't dropped.
+  const [headersText, setHeadersText] = useState(formatHeaders(settings.whisper_api_headers ?? {}));
+  const [testing, setTesting] = useState(false);
+  const [probe, setProbe] = useState<WhisperApiProbe | null>(null);
+  const [testError, setTestError] = useState<string | null>(null);
+
+  const handleTest = async () => {
+    setTesting(true);
+    setProbe(null);
+    setTestError(null);
+    try {
+      setProbe(await testWhisperApi());
+    } catch (e) {
+      setTestError(String(e));
+    } finally {
+      setTesting(false);
+    }
+  };
+
+  const check = (ok: boolean, label: string) => (
+    <span className={ok ? "text-success" : "text-error"}>
+      {ok ? "✓" : "✗"} {label}
+    </span>
+  );
+
   return (
     <div className="space-y-4">
       <h3 className="text-sm font-medium text-text">Whisper Configuration</h3>
@@ -34,6 +77,35 @@ export default function WhisperSettings({ settings, onChange }: WhisperSettingsP
         </label>
       </div>
 
+      <div className="space-y-1">
+        <label className="flex items-center gap-2 cursor-pointer">
+          <input
+            type="checkbox"
+            checked={settings.whisper_fallback}
+            onChange={(e) => onChange({ ...settings, whisper_fallback: e.target.checked })}
+            className="accent-accent"
+          />
+          <span className="text-sm text-text">
+            If transcription fails, retry with the{" "}
+            {settings.whisper_mode === "local" ? "API" : "local model"}
+          </span>
+        </label>
+        <p className="text-xs text-text-muted">
+          Order tried:{" "}
+          {[
+            settings.whisper_mode === "local" ? "local model" : "API",
+            ...(settings.whisper_fallback &&
+            (settings.whisper_mode === "api" || settings.whisper_api_endpoint.trim())
+              ? [settings.whisper_mode === "local" ? "API" : "local model"]
+              : []),
+          ].join(" → ")}
+          {settings.whisper_fallback &&
+            settings.whisper_mode === "local" &&
+            !settings.whisper_api_endpoint.trim() &&
+            " (switch to API to set an endpoint for the fallback)"}
+        </p>
+      </div>
+
       <div>
         <label className="block text-xs text-text-muted mb-1">Language</label>
         <select
@@ -66,10 +138,27 @@ export default function WhisperSettings({ settings, onChange }: WhisperSettingsP
       </div>
 
       {settings.whisper_mode === "local" ? (
-        <ModelDownload
-          currentModel={settings.whisper_model}
-          onModelLoaded={(model) => onChange({ ...settings, whisper_model: model })}
-        />
+        <div className="space-y-3">
+          <ModelDownload
+            currentModel={settings.whisper_model}
+            onModelLoaded={(model) => onChange({ ...settings, whisper_model: model })}
+          />
+          <div>
+            <label className="block text-xs text-text-muted mb-1">
+              Unload the model after this many idle minutes (0 = keep loaded)
+            </label>
+            <input
+              type="number"
+              min={0}
+              value={settings.whisper_idle_unload_mins}
+              onChange={(e) => onChange({ ...settings, whisper_idle_unload_mins: Number(e.target.value) })}
+              className="w-24 bg-bg border border-primary rounded px-3 py-2 text-text text-sm focus:outline-none focus:ring-1 focus:ring-accent"
+            />
+            <p className="text-xs text-text-muted mt-1">
+              Frees the memory while you're not dictating; it reloads when you press the hotkey.
+            </p>
+          </div>
+        </div>
       ) : (
         <div className="space-y-3">
           <div>
@@ -86,9 +175,9 @@ export default function WhisperSettings({ settings, onChange }: WhisperSettingsP
             <label className="block text-xs text-text-muted mb-1">API Key</label>
             <input
               type="password"
-              value={settings.whisper_api_key}
+              value={settings.whisper_api_key.startsWith("keyring:") ? "" : settings.whisper_api_key}
               onChange={(e) => onChange({ ...settings, whisper_api_key: e.target.value })}
-              placeholder="sk-..."
+              placeholder={settings.whisper_api_key.startsWith("keyring:") ? "Saved in the system keyring" : "sk-..."}
               className="w-full bg-bg border border-primary rounded px-3 py-2 text-text text-sm focus:outline-none focus:ring-1 focus:ring-accent"
             />
           </div>
@@ -107,6 +196,98 @@ export default function WhisperSettings({ settings, onChange }: WhisperSettingsP
               installed (e.g. <code>Systran/faster-whisper-large-v3</code>).
             </p>
           </div>
+          <div className="flex gap-3">
+            <div>
+              <label className="block text-xs text-text-muted mb-1">Protocol</label>
+              <select
+                value={settings.whisper_api_protocol}
+                onChange={(e) =>
+                  onChange({
+                    ...settings,
+                    whisper_api_protocol: e.target.value as AppSettings["whisper_api_protocol"],
+                  })
+                }
+                className="bg-bg border border-primary rounded px-3 py-2 text-text text-sm focus:outline-none focus:ring-1 focus:ring-accent"
+              >
+                <option value="openai">OpenAI-compatible</option>
+                <option value="whispercpp">whisper.cpp server</option>
+              </select>
+            </div>
+            <div className="flex-1">
+              <label className="block text-xs text-text-muted mb-1">Path (empty = protocol default)</label>
+              <input
+                type="text"
+                value={settings.whisper_api_path}
+                onChange={(e) => onChange({ ...settings, whisper_api_path: e.target.value })}
+                placeholder={
+                  settings.whisper_api_protocol === "whispercpp" ? "/inference" : "/v1/audio/transcriptions"
+                }
+                className="w-full bg-bg border border-primary rounded px-3 py-2 text-text text-sm focus:outline-none focus:ring-1 focus:ring-accent"
+              />
+            </div>
+            <div>
+              <label className="block text-xs text-text-muted mb-1">Timeout (s, 0 = none)</label>
+              <input
+                type="number"
+                min={0}
+                value={settings.whisper_api_timeout_secs}
+                onChange={(e) =>
+                  onChange({ ...settings, whisper_api_timeout_secs: Number(e.target.value) })
+                }
+                className="w-24 bg-bg border border-primary rounded px-3 py-2 text-text text-sm focus:outline-none focus:ring-1 focus:ring-accent"
+              />
+            </div>
+          </div>
+          <div>
+            <label className="block text-xs text-text-muted mb-1">
+              Extra headers (one <code>Name: value</code> per line)
+            </label>
+            <textarea
+              value={headersText}
+              onChange={(e) => setHeadersText(e.target.value)}
+              onBlur={() => onChange({ ...settings, whisper_api_headers: parseHeaders(headersText) })}
+              rows={2}
+              placeholder="X-Api-Token: ..."
+              className="w-full bg-bg border border-primary rounded px-3 py-2 text-text text-sm focus:outline-none focus:ring-1 focus:ring-accent resize-y"
+            />
+          </div>
+          <label className="flex items-center gap-2 cursor-pointer">
+            <input
+              type="checkbox"
+              checked={settings.whisper_api_verbose_json}
+              onChange={(e) => onChange({ ...settings, whisper_api_verbose_json: e.target.checked })}
+              className="accent-accent"
+            />
+            <span className="text-sm text-text">
+              Request segments and drop those the server marks as silence
+            </span>
+          </label>
+          <div className="space-y-1">
+            <button
+              onClick={handleTest}
+              disabled={testing || !settings.whisper_api_endpoint.trim()}
+              className="px-4 py-2 text-sm bg-primary rounded hover:bg-blue-700 disabled:opacity-50 transition-colors"
+            >
+              {testing ? "Testing..." : "Test Connection"}
+            </button>
+            {testError && <p className="text-xs text-error">Error: {testError}</p>}
+            {probe && (
+              <div className="text-xs space-y-1">
+                <div className="flex gap-3">
+                  {check(probe.reachable, "Reachable")}
+                  {check(probe.auth_ok, "API key accepted")}
+                  {check(probe.model_accepted, "Model accepted")}
+                  <span className="text-text-muted">
+                    {probe.latency_ms} ms{probe.status !== undefined && ` · HTTP ${probe.status}`}
+                  </span>
+                </div>
+                {probe.sample_text && (
+                  <p className="text-text-muted">Sample transcription: {probe.sample_text}</p>
+                )}
+                {probe.error && <p className="text-error">{probe.error}</p>}
+              </div>
+            )}
+          </div>
         </div>
       )}
     </div>
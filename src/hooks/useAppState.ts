@@ -1,4 +1,4 @@
-import { useState, useCallback } from "react";
+import { useState, useCallback, useRef } from "react";
 import { useTauriEvent } from "./useTauriEvent";
 import type { PipelineStatus, PipelineStatusEvent } from "../lib/types";
 
@@ -7,18 +7,28 @@ export function useAppState() {
   const [rawText, setRawText] = useState<string>("");
   const [cleanedText, setCleanedText] = useState<string>("");
   const [error, setError] = useState<string | null>(null);
+  const [warning, setWarning] = useState<string | null>(null);
+  const runId = useRef<string | null>(null);
 
   const handler = useCallback((event: PipelineStatusEvent) => {
+    // A new recording starts a new run; late events of earlier runs are stale.
+    if (event.status === "recording") {
+      runId.current = event.run_id;
+    } else if (event.run_id !== runId.current) {
+      return;
+    }
     setStatus(event.status);
     if (event.raw_text) setRawText(event.raw_text);
     if (event.cleaned_text) setCleanedText(event.cleaned_text);
     if (event.error) setError(event.error);
+    if (event.warning) setWarning(event.warning);
     if (event.status === "idle" || event.status === "recording") {
       setError(null);
+      setWarning(null);
     }
   }, []);
 
   useTauriEvent<PipelineStatusEvent>("pipeline-status", handler);
 
-  return { status, rawText, cleanedText, error, setStatus };
+  return { status, rawText, cleanedText, error, warning, setStatus };
 }
@@ -1,5 +1,13 @@
 import { invoke } from "@tauri-apps/api/core";
-import type { AudioDevice, TranscriptionRecord, AppSettings } from "./types";
+import type {
+  AudioDevice,
+  TranscriptionRecord,
+  AppSettings,
+  WhisperApiProbe,
+  LlmModelInfo,
+  ExampleSetFormat,
+  LatencyStats,
+} from "./types";
 
 export async function listAudioDevices(): Promise<AudioDevice[]> {
   return invoke("list_audio_devices");
@@ -41,6 +49,15 @@ export async function testLlmConnection(): Promise<string> {
   return invoke("test_llm_connection");
 }
 
+export async function listLlmModels(): Promise<LlmModelInfo[]> {
+  return invoke("list_llm_models");
+}
+
+// Ollama only. Progress arrives as "llm-model-pull-progress" events.
+export async function pullLlmModel(model: string): Promise<void> {
+  return invoke("pull_llm_model", { model });
+}
+
 export async function copyToClipboard(text: string): Promise<void> {
   return invoke("copy_to_clipboard", { text });
 }
@@ -121,6 +138,30 @@ export async function clearHistory(): Promise<void> {
   return invoke("clear_history");
 }
 
-export async function testWhisperApi(): Promise<string> {
+export async function getLatencyStats(): Promise<LatencyStats[]> {
+  return invoke("get_latency_stats");
+}
+
+export async function promoteHistoryItem(
+  id: string,
+  cleanedText?: string,
+): Promise<AppSettings> {
+  return invoke("promote_history_item", { id, cleanedText });
+}
+
+export async function exportFewShotExamples(format: ExampleSetFormat): Promise<string> {
+  return invoke("export_few_shot_examples", { format });
+}
+
+// Merges into the existing examples (same input = replaced) unless `replace`.
+export async function importFewShotExamples(
+  contents: string,
+  format: ExampleSetFormat,
+  replace: boolean,
+): Promise<AppSettings> {
+  return invoke("import_few_shot_examples", { contents, format, replace });
+}
+
+export async function testWhisperApi(): Promise<WhisperApiProbe> {
   return invoke("test_whisper_api");
 }
@@ -1,6 +1,7 @@
 export type PipelineStatus =
   | "idle"
   | "recording"
+  | "queued"
   | "transcribing"
   | "cleaning"
   | "done"
@@ -26,6 +27,17 @@ export interface TranscriptionRecord {
   created_at: string;
   duration_secs: number;
   model_used: string;
+  llm_model: string;
+  // Milliseconds per step ("stop", "transcribe", "cleanup", "output", ..., "total").
+  timings: Record<string, number>;
+}
+
+export interface LatencyStats {
+  step: string;
+  model: string;
+  count: number;
+  p50_ms: number;
+  p95_ms: number;
 }
 
 export interface FewShotExample {
@@ -33,32 +45,152 @@ export interface FewShotExample {
   output: string;
 }
 
+export type FewShotSelection = "first" | "similar";
+
+export type ExampleSetFormat = "json" | "yaml";
+
+export interface AppPromptRule {
+  pattern: string;
+  system_prompt: string;
+}
+
+export interface GenerationOptions {
+  temperature: number;
+  top_p: number | null;
+  max_tokens: number | null;
+  num_ctx: number | null;
+  keep_alive: string;
+  seed: number | null;
+  think: boolean;
+}
+
+export interface ReplaceRule {
+  find: string;
+  replace: string;
+  case_sensitive: boolean;
+}
+
+export type StageConfig =
+  | { type: "cleanup" }
+  | { type: "replace"; rules: ReplaceRule[] }
+  | { type: "filter"; min_words: number; ignore: string[] }
+  | { type: "script"; command: string; args: string[]; timeout_secs: number }
+  | { type: "output" }
+  | { type: "history" };
+
+export interface PipelineProfile {
+  name: string;
+  stages: StageConfig[];
+}
+
+export type LlmApiType = "ollama" | "openai" | "anthropic" | "llamacpp" | "lmstudio";
+
 export interface LlmConfig {
   endpoint: string;
   model: string;
   system_prompt: string;
-  api_type: "ollama" | "openai";
+  api_type: LlmApiType;
   few_shot_examples: FewShotExample[];
+  few_shot_limit: number;
+  few_shot_selection: FewShotSelection;
+  stream: boolean;
+  timeout_secs: number;
+  max_retries: number;
+  breaker_threshold: number;
+  breaker_cooldown_secs: number;
+  api_key: string;
+  organization: string;
+  extra_headers: Record<string, string>;
+  faithfulness_check: boolean;
+  app_context: boolean;
+  app_rules: AppPromptRule[];
+  rewrite_prompt: string;
+  options: GenerationOptions;
+  warm_up: boolean;
 }
 
 export interface AppSettings {
   audio_device_index: number | null;
+  max_recording_secs: number;
   hotkey: string;
+  rewrite_hotkey: string;
   whisper_mode: "local" | "api";
   whisper_model: string;
   whisper_language: string;
+  whisper_idle_unload_mins: number;
   whisper_api_endpoint: string;
   whisper_api_key: string;
   whisper_api_model: string;
+  whisper_api_protocol: "openai" | "whispercpp";
+  whisper_api_path: string;
+  whisper_api_headers: Record<string, string>;
+  whisper_api_verbose_json: boolean;
+  whisper_api_timeout_secs: number;
+  whisper_fallback: boolean;
   llm: LlmConfig;
   auto_paste: boolean;
+  progressive_paste: boolean;
   paste_shortcut: string;
+  copy_shortcut: string;
   history_max_items: number;
+  pipeline_profiles: PipelineProfile[];
+  active_profile: string;
 }
 
+export interface LlmModelInfo {
+  name: string;
+  size_bytes?: number;
+  context_length?: number;
+  parameter_size?: string;
+  quantization?: string;
+}
+
+export interface LlmPullProgress {
+  model: string;
+  status: string;
+  progress: number;
+  completed?: number;
+  total?: number;
+}
+
+export interface WhisperApiProbe {
+  reachable: boolean;
+  auth_ok: boolean;
+  model_accepted: boolean;
+  latency_ms: number;
+  status?: number;
+  sample_text?: string;
+  error?: string;
+}
+
+/** `AppError` variants and the two ways a run ends with nothing to output. */
+export type PipelineErrorCode =
+  | "audio"
+  | "whisper"
+  | "llm"
+  | "settings"
+  | "history"
+  | "hotkey"
+  | "output"
+  | "io"
+  | "other"
+  | "no_speech"
+  | "dropped";
+
 export interface PipelineStatusEvent {
+  /** Same for every event of a run, from "recording" to "done" or "error". */
+  run_id: string;
   status: PipelineStatus;
+  profile?: string;
+  backend?: string;
+  /** Milliseconds per finished step. */
+  timings?: Record<string, number>;
+  /** Input level (RMS, 0 to 1) while recording. */
+  level?: number;
   raw_text?: string;
   cleaned_text?: string;
   error?: string;
+  error_code?: PipelineErrorCode;
+  /** A problem the run recovered from; unlike `error`, the run goes on. */
+  warning?: string;
 }